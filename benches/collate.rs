@@ -0,0 +1,98 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    budgetron::{
+        loading::{Money, Transaction, TransactionType},
+        processing::{Collate, RefundCollator, TransferCollator, TransferOptions, Window},
+    },
+    budgetronlib::fintime::{Date, Timeframe},
+    criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput},
+};
+
+/// About ten years of transactions over a few accounts, with roughly one in
+/// ten paid back by a matching credit a few days later.
+fn history(len: usize) -> Vec<Transaction> {
+    let mut seed: u64 = 42;
+    let mut next = |n: u64| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) % n
+    };
+    let accounts = ["Checking", "Savings", "Visa", "Amex"];
+    let per_day = len as u64 / 3650 + 1;
+
+    let mut transactions = Vec::with_capacity(len);
+    while transactions.len() < len {
+        let day = (transactions.len() as u64 / per_day) as i64;
+        let date = Date::ymd(2010, 1, 1) + Timeframe::Days(day);
+        let amount = Money::from_i64(next(5000) as i64 + 1);
+        let account = accounts[next(4) as usize];
+        transactions.push(Transaction {
+            date,
+            description: format!("PURCHASE {}", next(1000)),
+            amount,
+            transaction_type: TransactionType::Debit,
+            account_name: account.to_owned(),
+            ..Default::default()
+        });
+        if next(10) == 0 {
+            transactions.push(Transaction {
+                date: date + Timeframe::Days(next(4) as i64),
+                description: "PAYMENT".to_owned(),
+                amount,
+                transaction_type: TransactionType::Credit,
+                account_name: accounts[next(4) as usize].to_owned(),
+                ..Default::default()
+            });
+        }
+    }
+    transactions.sort_by_key(|t| t.date);
+    transactions.truncate(len);
+    transactions
+}
+
+fn collate<C: Collate>(collator: &C, transactions: &[Transaction]) -> Vec<Transaction> {
+    collator
+        .collate(transactions.to_vec(), &mut Vec::new())
+        .expect("Unable to collate")
+}
+
+fn bench_collators(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collate");
+    group.sample_size(10);
+    for &len in &[25_000, 50_000, 100_000, 200_000] {
+        let transactions = history(len);
+        group.throughput(Throughput::Elements(len as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("transfer_horizon", len),
+            &transactions,
+            |b, transactions| b.iter(|| collate(&TransferCollator::new(100), transactions)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("transfer_window", len),
+            &transactions,
+            |b, transactions| {
+                let collator =
+                    TransferCollator::with_options(Window::Days(5), TransferOptions::default());
+                b.iter(|| collate(&collator, transactions))
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("refund_horizon", len),
+            &transactions,
+            |b, transactions| b.iter(|| collate(&RefundCollator::new(100), transactions)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_collators);
+criterion_main!(benches);
@@ -10,7 +10,15 @@ use {
     crate::error::{BResult, BudgetError},
     serde::de::DeserializeOwned,
     serde::Deserialize,
-    std::{collections::HashMap, env, fs::File, io::Read, path::PathBuf},
+    std::{
+        collections::HashMap,
+        env, fmt,
+        fs::File,
+        io::Read,
+        path::{Path, PathBuf},
+        process::Command,
+        str::FromStr,
+    },
     toml,
 };
 
@@ -42,35 +50,115 @@ impl CategoryConfig {
     }
 }
 
+/// The file `load_cfg` reads: the closest `fname` in the current directory or
+/// one of its parents, or the one in the home directory if there is no current
+/// directory.
+pub fn find_cfg(fname: &str) -> Option<PathBuf> {
+    if let Ok(mut dir) = env::current_dir() {
+        while dir.file_name().is_some() {
+            let path = dir.join(fname);
+            if path.is_file() {
+                return Some(path);
+            }
+            dir.pop();
+        }
+        None
+    } else {
+        let path = dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("/"))
+            .join(fname);
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
+
 pub fn load_cfg<Cfg>(fname: &str) -> BResult<Cfg>
 where
     Cfg: DeserializeOwned,
 {
-    let config_contents = {
-        if let Ok(mut dir) = env::current_dir() {
-            let mut contents = "".to_owned();
-            while dir.file_name() != None {
-                if let Ok(mut f) = File::open(dir.join(fname)) {
-                    let _ = f.read_to_string(&mut contents);
-                    break;
-                }
-                dir.pop();
-            }
-            contents
-        } else {
-            let path = dirs::home_dir()
-                .unwrap_or_else(|| PathBuf::from("/"))
-                .join(fname);
-            let ret: String = if let Ok(mut f) = File::open(path) {
-                let mut s = String::new();
-                let _ = f.read_to_string(&mut s);
-                s
-            } else {
-                "".to_owned()
-            };
-            ret
+    let mut config_contents = "".to_owned();
+    if let Some(path) = find_cfg(fname) {
+        if let Ok(mut f) = File::open(path) {
+            let _ = f.read_to_string(&mut config_contents);
         }
-    };
+    }
 
     Ok(toml::from_str(&config_contents)?)
 }
+
+/// Where to read a configuration from, e.g. to compare two versions of it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigSource {
+    /// The file `load_cfg` would read.
+    Current,
+    /// Any file.
+    File(PathBuf),
+    /// The file `load_cfg` would read, as it was at a git revision.
+    Git(String),
+}
+
+impl FromStr for ConfigSource {
+    type Err = BudgetError;
+
+    /// `git:<revision>` for a git revision, and a path otherwise.
+    fn from_str(s: &str) -> BResult<ConfigSource> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("git"), Some(revision)) => Ok(ConfigSource::Git(revision.to_owned())),
+            _ => Ok(ConfigSource::File(PathBuf::from(s))),
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigSource::Current => write!(f, "current configuration"),
+            ConfigSource::File(ref path) => write!(f, "{}", path.display()),
+            ConfigSource::Git(ref revision) => write!(f, "git:{}", revision),
+        }
+    }
+}
+
+impl ConfigSource {
+    pub fn load<Cfg>(&self, fname: &str) -> BResult<Cfg>
+    where
+        Cfg: DeserializeOwned,
+    {
+        match *self {
+            ConfigSource::Current => load_cfg(fname),
+            ConfigSource::File(ref path) => {
+                let mut config_contents = String::new();
+                File::open(path)?.read_to_string(&mut config_contents)?;
+                Ok(toml::from_str(&config_contents)?)
+            }
+            ConfigSource::Git(ref revision) => {
+                let config_contents = git_show(fname, revision)?;
+                Ok(toml::from_str(&config_contents)?)
+            }
+        }
+    }
+}
+
+fn git_show(fname: &str, revision: &str) -> BResult<String> {
+    let path = find_cfg(fname)
+        .ok_or_else(|| BudgetError::ConfigError(format!("Unable to find {}", fname)))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    let output = Command::new("git")
+        .arg("show")
+        .arg(format!("{}:./{}", revision, fname))
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(BudgetError::ConfigError(format!(
+            "Unable to read {} at {}: {}",
+            fname,
+            revision,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|e| BudgetError::ConfigError(e.to_string()))
+}
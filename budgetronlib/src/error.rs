@@ -7,8 +7,9 @@
 // except according to those terms.
 
 use {
+    crate::fintime::Date,
     csv,
-    std::{convert::From, io},
+    std::{convert::From, fmt, io},
     toml,
 };
 
@@ -16,14 +17,126 @@ use {
 pub enum BudgetError {
     CSVError(csv::Error),
     NoCategoryFoundError(String),
+    ParseError(String),
+    FieldError {
+        line: u64,
+        column: Option<u64>,
+        field: Option<String>,
+        message: String,
+    },
     TomlDeError(toml::de::Error),
     IOError(io::Error),
-    NoMatchingImporter,
+    NoMatchingImporter {
+        file: String,
+        attempts: Vec<ImportAttempt>,
+    },
+    UnknownFormat(String),
+    NoExchangeRate {
+        from: String,
+        to: String,
+        date: Date,
+    },
+    MixedCurrencies(Vec<String>),
+    ConfigError(String),
     NoTransactionError,
 
     Multi(Vec<BudgetError>),
 }
 
+/// The reason a single importer could not load a file.
+#[derive(Debug)]
+pub struct ImportAttempt {
+    pub importer: String,
+    pub error: BudgetError,
+}
+
+impl ImportAttempt {
+    /// How far into the file the importer got before failing.
+    pub fn progress(&self) -> u64 {
+        match self.error {
+            BudgetError::FieldError { line, .. } => line,
+            BudgetError::CSVError(ref e) => e.position().map(|p| p.line()).unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for ImportAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.importer, self.error)
+    }
+}
+
+impl fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BudgetError::CSVError(ref e) => write!(f, "{}", e),
+            BudgetError::NoCategoryFoundError(ref category) => {
+                write!(f, "No category found for '{}'", category)
+            }
+            BudgetError::ParseError(ref message) => write!(f, "{}", message),
+            BudgetError::FieldError {
+                line,
+                column,
+                ref field,
+                ref message,
+            } => {
+                write!(f, "line {}", line)?;
+                if let Some(column) = column {
+                    write!(f, ", column {}", column)?;
+                }
+                if let Some(ref field) = *field {
+                    write!(f, " ({})", field)?;
+                }
+                write!(f, ": {}", message)
+            }
+            BudgetError::TomlDeError(ref e) => write!(f, "Invalid configuration: {}", e),
+            BudgetError::IOError(ref e) => write!(f, "{}", e),
+            BudgetError::NoMatchingImporter {
+                ref file,
+                ref attempts,
+            } => {
+                let mut attempts = attempts.iter().collect::<Vec<_>>();
+                attempts.sort_by_key(|attempt| ::std::cmp::Reverse(attempt.progress()));
+                write!(f, "Unable to load {}", file)?;
+                if let Some((closest, rest)) = attempts.split_first() {
+                    write!(f, "\n  closest match was {}", closest)?;
+                    for attempt in rest {
+                        write!(f, "\n  {}", attempt)?;
+                    }
+                }
+                Ok(())
+            }
+            BudgetError::UnknownFormat(ref format) => {
+                write!(f, "Unknown input format '{}'", format)
+            }
+            BudgetError::NoExchangeRate {
+                ref from,
+                ref to,
+                date,
+            } => write!(
+                f,
+                "No exchange rate from {} to {} on or before {}",
+                from, to, date
+            ),
+            BudgetError::MixedCurrencies(ref currencies) => write!(
+                f,
+                "Transactions are in several currencies ({}); \
+                 add a processor with a reporting_currency to convert them",
+                currencies.join(", ")
+            ),
+            BudgetError::ConfigError(ref message) => write!(f, "{}", message),
+            BudgetError::NoTransactionError => write!(f, "No transactions found"),
+            BudgetError::Multi(ref errors) => {
+                for error in errors {
+                    writeln!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 impl From<csv::Error> for BudgetError {
     fn from(e: csv::Error) -> BudgetError {
         BudgetError::CSVError(e)
@@ -10,7 +10,7 @@ use {
     self::Timeframe::*,
     chrono::{self, offset::TimeZone, Datelike},
     serde::{de, Deserialize, Deserializer, Serialize, Serializer},
-    std::{fmt, ops},
+    std::{fmt, ops, str::FromStr},
 };
 
 pub fn is_leap_year(year: i64) -> bool {
@@ -124,6 +124,11 @@ impl Date {
         self.date.day()
     }
 
+    /// The day of the week, counting from 0 for Monday to 6 for Sunday.
+    pub fn weekday(self) -> u32 {
+        self.date.weekday().num_days_from_monday()
+    }
+
     pub fn uid(self) -> String {
         format!(
             "{:04}{:02}{:02}",
@@ -197,6 +202,15 @@ impl Date {
     pub fn year(self) -> i32 {
         self.date.year() as i32
     }
+
+    /// Parses a date using a strftime-style format such as `%d.%m.%Y`.
+    pub fn parse_with_format(value: &str, format: &str) -> Result<Date, String> {
+        chrono::NaiveDate::parse_from_str(value, format)
+            .map(|date| Date {
+                date: chrono::Utc.from_utc_date(&date),
+            })
+            .map_err(|e| format!("Unable to parse date '{}' as '{}': {}", value, format, e))
+    }
 }
 
 macro_rules! forward_ref_binop {
@@ -333,6 +347,14 @@ impl<'de> de::Visitor<'de> for DateVisitor {
     }
 }
 
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Date, String> {
+        de::Visitor::visit_str::<de::value::Error>(DateVisitor, s).map_err(|e| e.to_string())
+    }
+}
+
 impl<'de> Deserialize<'de> for Date {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
         d.deserialize_str(DateVisitor)
@@ -10,29 +10,53 @@
 
 use {
     budgetron::{
-        loading,
-        processing::{collate_all, Collator, ConfiguredProcessors},
+        loading::{self, Transaction},
+        processing::{
+            self, collate_all, reconcile, CategoryClassifier, CollatedTransactions, Collator,
+            ConfiguredBalances, ConfiguredProcessors, SuggestOptions,
+        },
         reporting::{ConfiguredReports, List, Reporter},
     },
-    budgetronlib::config,
+    budgetronlib::{
+        config::{self, ConfigSource},
+        error::BResult,
+    },
     iron::prelude::*,
+    log::info,
     mount::Mount,
     serde::Serialize,
-    std::{borrow::Cow, path::Path},
+    std::{borrow::Cow, path::Path, process},
     structopt::StructOpt,
 };
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "Budgetron", about = "Processes transactions into reports")]
 struct Opt {
+    /// A file to load. Prefix it with a format (ofx, qif, budgetron, mint, logix, alliant, or the
+    /// name of a configured importer) to skip format inference, e.g. `-f mint:transactions.csv`.
     #[structopt(short = "f", long = "file")]
-    input_files: Vec<String>,
+    input_files: Vec<loading::InputFile>,
 
     #[structopt(short, long)]
     serve: bool,
 
     #[structopt(short, long, default_value = "3000")]
     port: u32,
+
+    /// Instead of reporting, show how processing the files with this configuration differs from
+    /// processing them with `--diff-to`. Either a path, or `git:<revision>` for budgetronrc.toml
+    /// as of a git revision. Defaults to the current budgetronrc.toml.
+    #[structopt(long = "diff-from")]
+    diff_from: Option<ConfigSource>,
+
+    /// The configuration to compare `--diff-from` with. Defaults to the current budgetronrc.toml.
+    #[structopt(long = "diff-to")]
+    diff_to: Option<ConfigSource>,
+
+    /// Instead of reporting, list the categories suggested for uncategorized transactions that
+    /// were not sure enough to be filled in, least sure first.
+    #[structopt(long = "review-suggestions")]
+    review_suggestions: bool,
 }
 
 #[cfg(feature = "db")]
@@ -43,20 +67,74 @@ fn main() {
 
     let opt = Opt::from_args();
 
-    let transactions =
-        loading::load_from_files(opt.input_files.into_iter()).expect("Unable to load files");
+    let importers: loading::ConfiguredImporters =
+        config::load_cfg("budgetronrc.toml").expect("Configured Importers failed to load");
+    let loaded = match loading::load_from_files(opt.input_files.into_iter(), &importers) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if !loaded.duplicates.is_empty() {
+        eprintln!(
+            "Skipped {} transactions that were already loaded from another file",
+            loaded.duplicates.len()
+        );
+        for duplicate in &loaded.duplicates {
+            info!("Skipped {}", duplicate);
+        }
+    }
+    let transactions = loaded.transactions;
+
+    if opt.diff_from.is_some() || opt.diff_to.is_some() {
+        let from = opt.diff_from.unwrap_or(ConfigSource::Current);
+        let to = opt.diff_to.unwrap_or(ConfigSource::Current);
+        if let Err(e) = print_diff(&transactions, &from, &to) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
 
     let processors: ConfiguredProcessors =
         config::load_cfg("budgetronrc.toml").expect("Configured Processors failed to load");
-    let transactions =
-        collate_all(transactions, &[Collator::Config(processors)]).expect("Unable to collate");
+    let suggest_options = processors.suggest_options().cloned().unwrap_or_default();
+    let collated = match collate_all(transactions, &[Collator::Config(processors)]) {
+        Ok(collated) => collated,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let balances: ConfiguredBalances =
+        config::load_cfg("budgetronrc.toml").expect("Configured Balances failed to load");
+    for divergence in reconcile(
+        &collated.transactions,
+        &collated.dropped,
+        balances.assertions(),
+    ) {
+        eprintln!("Balance mismatch: {}", divergence);
+    }
+
+    let transactions = collated.transactions;
+
+    if opt.review_suggestions {
+        print_suggestions(&transactions, &suggest_options);
+        return;
+    }
+
     let cow_transactions = transactions
         .iter()
         .map(|t| Cow::Borrowed(t))
         .collect::<Vec<_>>();
 
+    // Amortized portions can be dated months ahead, so only the transactions
+    // that actually happened decide where the reports end.
     let end_date = cow_transactions
         .iter()
+        .filter(|t| t.amortized_from.is_none())
         .map(|t| t.date)
         .max()
         .expect("No end date found");
@@ -70,10 +148,12 @@ fn main() {
         .map(|t| Cow::Borrowed(t))
         .collect::<Vec<_>>();
     #[cfg(feature = "db")]
-    let transaction_list = (List, Database).report(cow_transactions.into_iter(), end_date);
+    let transaction_list =
+        (List::new(&collated.dropped), Database).report(cow_transactions.into_iter(), end_date);
 
     #[cfg(not(feature = "db"))]
-    let transaction_list = List.report(cow_transactions.into_iter(), end_date);
+    let transaction_list =
+        List::new(&collated.dropped).report(cow_transactions.into_iter(), end_date);
 
     if opt.serve {
         let mut mount = Mount::new();
@@ -91,6 +171,82 @@ fn main() {
     }
 }
 
+fn print_diff(transactions: &[Transaction], from: &ConfigSource, to: &ConfigSource) -> BResult<()> {
+    let run = |source: &ConfigSource| -> BResult<(CollatedTransactions, ConfiguredReports)> {
+        let processors: ConfiguredProcessors = source.load("budgetronrc.toml")?;
+        let reports: ConfiguredReports = source.load("budgetronrc.toml")?;
+        let collated = collate_all(transactions.to_vec(), &[Collator::Config(processors)])?;
+        Ok((collated, reports))
+    };
+    let (before, before_reports) = run(from)?;
+    let (after, after_reports) = run(to)?;
+
+    println!("Changes from {} to {}:", from, to);
+    let diffs = processing::diff(&before, &after);
+    for diff in &diffs {
+        println!("{}", diff);
+    }
+    println!("{} transactions changed", diffs.len());
+
+    let end_date = match transactions.iter().map(|t| t.date).max() {
+        Some(end_date) => end_date,
+        None => return Ok(()),
+    };
+    let before_totals =
+        before_reports.totals(before.transactions.iter().map(Cow::Borrowed), end_date);
+    let after_totals = after_reports.totals(after.transactions.iter().map(Cow::Borrowed), end_date);
+    println!("\nReport totals:");
+    for (name, after) in &after_totals {
+        match before_totals
+            .iter()
+            .find(|(before_name, _)| before_name == name)
+        {
+            Some((_, before)) if before == after => {}
+            Some((_, before)) => println!(
+                "  {}: in {} -> {}, out {} -> {}, net {} -> {}",
+                name, before.credit, after.credit, before.debit, after.debit, before.net, after.net
+            ),
+            None => println!("  {}: added", name),
+        }
+    }
+    for (name, _) in &before_totals {
+        if !after_totals
+            .iter()
+            .any(|(after_name, _)| after_name == name)
+        {
+            println!("  {}: removed", name);
+        }
+    }
+    Ok(())
+}
+
+fn print_suggestions(transactions: &[Transaction], options: &SuggestOptions) {
+    let classifier = CategoryClassifier::train(transactions, options);
+    let mut suggestions = transactions
+        .iter()
+        .filter(|t| options.is_uncategorized(t))
+        .filter_map(|t| classifier.suggest(t).map(|suggestion| (t, suggestion)))
+        .filter(|(_, suggestion)| suggestion.confidence < options.threshold)
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|(_, a), (_, b)| {
+        a.confidence
+            .partial_cmp(&b.confidence)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    for (t, suggestion) in &suggestions {
+        println!(
+            "{} {} {} ({}): {} ({:.0}%)",
+            t.date,
+            t.original_description,
+            t.amount,
+            t.account_name,
+            suggestion.category,
+            suggestion.confidence * 100.0
+        );
+    }
+    println!("{} suggestions to review", suggestions.len());
+}
+
 struct JsonHandler<T: Serialize> {
     data: T,
 }
@@ -55,6 +55,7 @@ impl Genericize for AlliantExport {
     fn genericize(self) -> BResult<Transaction> {
         Ok(Transaction {
             uid: Some(self.id),
+            id: "".to_owned(),
             date: self.posted_at,
             person: "".to_owned(),
             description: self.nickname,
@@ -68,6 +69,13 @@ impl Genericize for AlliantExport {
             notes: "".to_owned(),
             transfer_destination_account: None,
             tags: vec![],
+            currency: None,
+            balance: None,
+            provenance: vec![],
+            refunded_by: vec![],
+            splits: vec![],
+            amortized_from: None,
+            owner_shares: vec![],
         })
     }
 }
@@ -0,0 +1,394 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::{
+        generic::{Transaction, TransactionType},
+        locale::{DateFormat, NumberFormat},
+        money::Money,
+    },
+    budgetronlib::{
+        error::{BResult, BudgetError},
+        fintime::Date,
+    },
+    csv::{ReaderBuilder, StringRecord},
+    serde::Deserialize,
+    std::io::Read,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ConfiguredImporters {
+    #[serde(default)]
+    importer: Vec<ImporterConfig>,
+    #[serde(default = "default_duplicate_window")]
+    duplicate_window: i64,
+}
+
+fn default_duplicate_window() -> i64 {
+    3
+}
+
+impl Default for ConfiguredImporters {
+    fn default() -> ConfiguredImporters {
+        ConfiguredImporters {
+            importer: Vec::new(),
+            duplicate_window: default_duplicate_window(),
+        }
+    }
+}
+
+impl ConfiguredImporters {
+    pub fn importers(&self) -> &[ImporterConfig] {
+        &self.importer
+    }
+
+    /// How many days apart the same transaction may be posted in two
+    /// overlapping exports.
+    pub fn duplicate_window(&self) -> i64 {
+        self.duplicate_window
+    }
+}
+
+/// Identifies a CSV column either by its header name or by its (zero based)
+/// position.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountSign {
+    #[default]
+    NegativeIsDebit,
+    PositiveIsDebit,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImporterConfig {
+    name: String,
+
+    date: Column,
+    description: Column,
+    amount: Option<Column>,
+    debit: Option<Column>,
+    credit: Option<Column>,
+    #[serde(default)]
+    amount_sign: AmountSign,
+    balance: Option<Column>,
+
+    account_column: Option<Column>,
+    account_name: Option<String>,
+
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+    date_format: Option<DateFormat>,
+    #[serde(flatten)]
+    number_format: NumberFormat,
+    #[serde(default)]
+    skip_rows: usize,
+    #[serde(default = "default_true")]
+    has_headers: bool,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_true() -> bool {
+    true
+}
+
+struct Columns {
+    date: usize,
+    description: usize,
+    amount: Option<usize>,
+    debit: Option<usize>,
+    credit: Option<usize>,
+    balance: Option<usize>,
+    account: Option<usize>,
+}
+
+/// A single record of the file being imported, along with enough context to
+/// say where a bad value came from.
+struct Row<'r> {
+    record: &'r StringRecord,
+    headers: Option<&'r StringRecord>,
+    line: u64,
+}
+
+impl<'r> Row<'r> {
+    fn error(&self, index: usize, message: String) -> BudgetError {
+        BudgetError::FieldError {
+            line: self.line,
+            column: Some(index as u64 + 1),
+            field: self
+                .headers
+                .and_then(|headers| headers.get(index))
+                .map(|header| header.trim().to_owned()),
+            message,
+        }
+    }
+
+    fn get(&self, index: usize) -> BResult<&'r str> {
+        self.record
+            .get(index)
+            .map(str::trim)
+            .ok_or_else(|| self.error(index, "row is missing this column".to_owned()))
+    }
+
+    fn money(&self, index: usize, format: &NumberFormat) -> BResult<Option<Money>> {
+        let value = self.get(index)?;
+        if value.is_empty() {
+            Ok(None)
+        } else {
+            format
+                .parse_money(value)
+                .map(Some)
+                .map_err(|e| self.error(index, e))
+        }
+    }
+}
+
+impl ImporterConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn resolve(&self, column: &Column, headers: Option<&StringRecord>) -> BResult<usize> {
+        match *column {
+            Column::Index(index) => Ok(index),
+            Column::Name(ref name) => headers
+                .and_then(|headers| headers.iter().position(|h| h.trim() == name))
+                .ok_or_else(|| BudgetError::ParseError(format!("no column named '{}'", name))),
+        }
+    }
+
+    fn resolve_optional(
+        &self,
+        column: &Option<Column>,
+        headers: Option<&StringRecord>,
+    ) -> BResult<Option<usize>> {
+        match *column {
+            Some(ref column) => Ok(Some(self.resolve(column, headers)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn columns(&self, headers: Option<&StringRecord>) -> BResult<Columns> {
+        if self.amount.is_none() && self.debit.is_none() && self.credit.is_none() {
+            return Err(BudgetError::ParseError(
+                "one of amount, debit or credit is required".to_owned(),
+            ));
+        }
+        Ok(Columns {
+            date: self.resolve(&self.date, headers)?,
+            description: self.resolve(&self.description, headers)?,
+            amount: self.resolve_optional(&self.amount, headers)?,
+            debit: self.resolve_optional(&self.debit, headers)?,
+            credit: self.resolve_optional(&self.credit, headers)?,
+            balance: self.resolve_optional(&self.balance, headers)?,
+            account: self.resolve_optional(&self.account_column, headers)?,
+        })
+    }
+
+    fn date(&self, row: &Row, index: usize) -> BResult<Date> {
+        let value = row.get(index)?;
+        match self.date_format {
+            Some(ref format) => format.parse(value),
+            None => value.parse(),
+        }
+        .map_err(|e| row.error(index, e))
+    }
+
+    fn amount(&self, columns: &Columns, row: &Row) -> BResult<(Money, TransactionType)> {
+        if let Some(index) = columns.amount {
+            if let Some(amount) = row.money(index, &self.number_format)? {
+                // Zero is a credit either way.
+                let is_debit = match self.amount_sign {
+                    AmountSign::NegativeIsDebit => amount < Money::zero(),
+                    AmountSign::PositiveIsDebit => amount > Money::zero(),
+                };
+                return Ok(if is_debit {
+                    (amount.abs(), TransactionType::Debit)
+                } else {
+                    (amount.abs(), TransactionType::Credit)
+                });
+            }
+        }
+        if let Some(index) = columns.debit {
+            if let Some(amount) = row.money(index, &self.number_format)? {
+                return Ok((amount.abs(), TransactionType::Debit));
+            }
+        }
+        if let Some(index) = columns.credit {
+            if let Some(amount) = row.money(index, &self.number_format)? {
+                return Ok((amount.abs(), TransactionType::Credit));
+            }
+        }
+        Err(BudgetError::FieldError {
+            line: row.line,
+            column: None,
+            field: None,
+            message: "row has no amount".to_owned(),
+        })
+    }
+
+    fn transaction(&self, columns: &Columns, row: &Row) -> BResult<Transaction> {
+        let description = row.get(columns.description)?.to_owned();
+        let (amount, transaction_type) = self.amount(columns, row)?;
+        let account_name = match columns.account {
+            Some(index) => row.get(index)?.to_owned(),
+            None => self
+                .account_name
+                .clone()
+                .unwrap_or_else(|| self.name.clone()),
+        };
+        let balance = match columns.balance {
+            Some(index) => row.money(index, &self.number_format)?,
+            None => None,
+        };
+        Ok(Transaction {
+            date: self.date(row, columns.date)?,
+            original_description: description.clone(),
+            description,
+            amount,
+            transaction_type,
+            account_name,
+            balance,
+            ..Default::default()
+        })
+    }
+
+    pub fn load<R: Read>(&self, reader: &mut R) -> BResult<Vec<Transaction>> {
+        if !self.delimiter.is_ascii() {
+            return Err(BudgetError::ParseError(
+                "delimiter must be a single ASCII character".to_owned(),
+            ));
+        }
+        self.number_format
+            .validate()
+            .map_err(BudgetError::ParseError)?;
+
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        let body = contents
+            .splitn(self.skip_rows + 1, '\n')
+            .nth(self.skip_rows)
+            .unwrap_or("");
+
+        let mut csv = ReaderBuilder::new()
+            .delimiter(self.delimiter as u8)
+            .has_headers(self.has_headers)
+            .flexible(true)
+            .from_reader(body.as_bytes());
+        let headers = if self.has_headers {
+            Some(csv.headers()?.clone())
+        } else {
+            None
+        };
+        let columns = self.columns(headers.as_ref())?;
+
+        let mut transactions = Vec::new();
+        for record in csv.records() {
+            let record = record?;
+            let row = Row {
+                record: &record,
+                headers: headers.as_ref(),
+                line: record.position().map(|p| p.line()).unwrap_or(0) + self.skip_rows as u64,
+            };
+            transactions.push(self.transaction(&columns, &row)?);
+        }
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn importer(config: &str) -> ImporterConfig {
+        toml::from_str(config).expect("Unable to parse importer config")
+    }
+
+    #[test]
+    fn named_columns_with_preamble() {
+        let importer = importer(
+            r#"
+            name = "Credit Union"
+            date = "Booked"
+            description = "Text"
+            debit = "Out"
+            credit = "In"
+            delimiter = ";"
+            date_format = "DD.MM.YYYY"
+            decimal_separator = ","
+            grouping_separator = "."
+            skip_rows = 2
+            "#,
+        );
+        let transactions = importer
+            .load(
+                &mut "Account statement\nGenerated today\nBooked;Text;Out;In\n31.12.2019;RENT;500,00;\n02.01.2020;SALARY;;1.000,00\n"
+                    .as_bytes(),
+            )
+            .expect("Unable to load transactions");
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].date, Date::ymd(2019, 12, 31));
+        assert_eq!(transactions[0].transaction_type, TransactionType::Debit);
+        assert_eq!(transactions[0].amount, Money::from_i64(500));
+        assert_eq!(transactions[0].account_name, "Credit Union");
+        assert_eq!(transactions[1].transaction_type, TransactionType::Credit);
+        assert_eq!(transactions[1].description, "SALARY");
+    }
+
+    #[test]
+    fn indexed_columns() {
+        let importer = importer(
+            r#"
+            name = "Card"
+            date = 0
+            description = 2
+            amount = 1
+            amount_sign = "positive_is_debit"
+            account_name = "Visa"
+            has_headers = false
+            "#,
+        );
+        let transactions = importer
+            .load(
+                &mut "1/2/2020,12.50,COFFEE\n1/3/2020,-20,PAYMENT\n1/4/2020,0,ADJUSTMENT\n"
+                    .as_bytes(),
+            )
+            .expect("Unable to load transactions");
+
+        assert_eq!(transactions[0].transaction_type, TransactionType::Debit);
+        assert_eq!(transactions[0].account_name, "Visa");
+        assert_eq!(transactions[1].transaction_type, TransactionType::Credit);
+        assert_eq!(transactions[1].amount, Money::from_i64(20));
+        assert_eq!(transactions[2].transaction_type, TransactionType::Credit);
+    }
+
+    #[test]
+    fn missing_header_fails() {
+        let importer = importer(
+            r#"
+            name = "Bank"
+            date = "Date"
+            description = "Payee"
+            amount = "Amount"
+            "#,
+        );
+        assert!(importer
+            .load(&mut "Date,Description,Amount\n".as_bytes())
+            .is_err());
+    }
+}
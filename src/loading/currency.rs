@@ -0,0 +1,76 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    serde::{
+        de::{self, Deserialize, Deserializer, Visitor},
+        ser::{Serialize, Serializer},
+    },
+    std::{fmt, str},
+};
+
+/// An ISO 4217 currency code such as `USD` or `EUR`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Currency([u8; 3]);
+
+impl str::FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Currency, String> {
+        let code = s.trim().as_bytes();
+        if code.len() != 3 || !code.iter().all(u8::is_ascii_alphabetic) {
+            return Err(format!("'{}' is not a three letter currency code", s));
+        }
+        Ok(Currency([
+            code[0].to_ascii_uppercase(),
+            code[1].to_ascii_uppercase(),
+            code[2].to_ascii_uppercase(),
+        ]))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Only ever constructed from ASCII letters.
+        write!(f, "{}", str::from_utf8(&self.0).unwrap_or("???"))
+    }
+}
+
+struct CurrencyVisitor;
+impl<'de> Visitor<'de> for CurrencyVisitor {
+    type Value = Currency;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a three letter currency code")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Currency, E>
+    where
+        E: de::Error,
+    {
+        value.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D>(deserializer: D) -> Result<Currency, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(CurrencyVisitor)
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
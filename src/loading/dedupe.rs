@@ -0,0 +1,247 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::{
+        generic::{Transaction, TransactionType},
+        money::Money,
+    },
+    std::{collections::HashMap, fmt},
+};
+
+#[derive(Debug)]
+pub enum DuplicateReason {
+    /// Both files gave the transaction the same importer provided id.
+    SameId(String),
+    /// Same account, amount and type, posted this many days apart.
+    Similar { days_apart: i64 },
+}
+
+/// A transaction that was skipped because an earlier file already contained
+/// it.
+#[derive(Debug)]
+pub struct Duplicate {
+    pub transaction: Transaction,
+    pub file: String,
+    pub original_file: String,
+    pub reason: DuplicateReason,
+}
+
+impl fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({}) in {} was already loaded from {}",
+            self.transaction.date,
+            self.transaction.description,
+            self.transaction.amount,
+            self.transaction.account_name,
+            self.file,
+            self.original_file
+        )?;
+        match self.reason {
+            DuplicateReason::SameId(ref id) => write!(f, " with id {}", id),
+            DuplicateReason::Similar { days_apart: 0 } => Ok(()),
+            DuplicateReason::Similar { days_apart } => {
+                write!(f, " {} day(s) apart", days_apart)
+            }
+        }
+    }
+}
+
+pub struct LoadedTransactions {
+    pub transactions: Vec<Transaction>,
+    pub duplicates: Vec<Duplicate>,
+}
+
+struct Kept {
+    transaction: Transaction,
+    file: usize,
+    /// The last file that matched this transaction. Each transaction can only
+    /// stand in for one transaction of any later file, so that a file which
+    /// really does contain two identical transactions keeps both of them.
+    matched_by: Option<usize>,
+}
+
+/// Merges the transactions of several files, dropping transactions that an
+/// earlier file already contained. Exports that overlap in time will repeat
+/// transactions, but two identical transactions within one file are assumed
+/// to be real and are always kept.
+///
+/// Transactions with importer provided ids are matched on those. Otherwise
+/// a transaction matches one in the same account with the same amount and
+/// type, posted at most `window` days apart.
+pub fn dedupe(files: Vec<(String, Vec<Transaction>)>, window: i64) -> LoadedTransactions {
+    let mut kept: Vec<Kept> = Vec::new();
+    let mut by_id: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    let mut by_amount: HashMap<(String, Money, TransactionType), Vec<usize>> = HashMap::new();
+    let mut duplicates = Vec::new();
+    let names = files
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    for (file, (name, transactions)) in files.into_iter().enumerate() {
+        let first_new = kept.len();
+        for transaction in transactions {
+            let available = |i: &usize| kept[*i].matched_by != Some(file);
+
+            let same_id = transaction.uid.as_ref().and_then(|uid| {
+                by_id
+                    .get(&(transaction.account_name.clone(), uid.clone()))
+                    .and_then(|candidates| candidates.iter().cloned().find(available))
+                    .map(|i| (i, DuplicateReason::SameId(uid.clone())))
+            });
+            let found = same_id.or_else(|| {
+                by_amount
+                    .get(&(
+                        transaction.account_name.clone(),
+                        transaction.amount,
+                        transaction.transaction_type,
+                    ))
+                    .and_then(|candidates| {
+                        candidates
+                            .iter()
+                            .cloned()
+                            .filter(available)
+                            .filter(|&i| {
+                                // Two different ids are two different transactions.
+                                transaction.uid.is_none() || kept[i].transaction.uid.is_none()
+                            })
+                            .map(|i| (i, (kept[i].transaction.date - transaction.date).abs()))
+                            .filter(|&(_, days_apart)| days_apart <= window)
+                            .min_by_key(|&(i, days_apart)| {
+                                (
+                                    days_apart,
+                                    kept[i].transaction.original_description
+                                        != transaction.original_description,
+                                )
+                            })
+                    })
+                    .map(|(i, days_apart)| (i, DuplicateReason::Similar { days_apart }))
+            });
+
+            match found {
+                Some((i, reason)) => {
+                    kept[i].matched_by = Some(file);
+                    duplicates.push(Duplicate {
+                        transaction,
+                        file: name.clone(),
+                        original_file: names[kept[i].file].clone(),
+                        reason,
+                    });
+                }
+                None => kept.push(Kept {
+                    transaction,
+                    file,
+                    matched_by: None,
+                }),
+            }
+        }
+
+        // Only index this file once it is done, so its transactions are never
+        // compared with each other.
+        for (i, k) in kept.iter().enumerate().skip(first_new) {
+            let t = &k.transaction;
+            if let Some(ref uid) = t.uid {
+                by_id
+                    .entry((t.account_name.clone(), uid.clone()))
+                    .or_default()
+                    .push(i);
+            }
+            by_amount
+                .entry((t.account_name.clone(), t.amount, t.transaction_type))
+                .or_default()
+                .push(i);
+        }
+    }
+
+    LoadedTransactions {
+        transactions: kept.into_iter().map(|k| k.transaction).collect(),
+        duplicates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, budgetronlib::fintime::Date};
+
+    fn t(day: i32, amount: i64, description: &str, uid: Option<&str>) -> Transaction {
+        Transaction {
+            uid: uid.map(str::to_owned),
+            date: Date::ymd(2019, 1, day),
+            description: description.to_owned(),
+            original_description: description.to_owned(),
+            amount: Money::from_i64(amount),
+            transaction_type: TransactionType::Debit,
+            account_name: "Checking".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn overlapping_exports() {
+        let loaded = dedupe(
+            vec![
+                (
+                    "december.csv".to_owned(),
+                    vec![
+                        t(1, 5, "COFFEE", None),
+                        t(1, 5, "COFFEE", None),
+                        t(2, 20, "GROCERIES", None),
+                    ],
+                ),
+                (
+                    "january.csv".to_owned(),
+                    vec![
+                        t(1, 5, "COFFEE", None),
+                        t(1, 5, "COFFEE", None),
+                        t(1, 5, "COFFEE", None),
+                        t(3, 20, "GROCERIES #12", None),
+                        t(9, 20, "GROCERIES", None),
+                    ],
+                ),
+            ],
+            3,
+        );
+
+        // Two of the three coffees and the groceries posted a day later were
+        // already loaded. The third coffee and the groceries a week later are
+        // new.
+        assert_eq!(loaded.transactions.len(), 5);
+        assert_eq!(loaded.duplicates.len(), 3);
+        assert!(loaded
+            .duplicates
+            .iter()
+            .all(|d| d.file == "january.csv" && d.original_file == "december.csv"));
+    }
+
+    #[test]
+    fn importer_ids() {
+        let loaded = dedupe(
+            vec![
+                (
+                    "a.csv".to_owned(),
+                    vec![t(1, 5, "COFFEE", Some("1")), t(1, 5, "COFFEE", Some("2"))],
+                ),
+                (
+                    "b.csv".to_owned(),
+                    vec![t(2, 5, "COFFEE", Some("2")), t(1, 5, "COFFEE", Some("3"))],
+                ),
+            ],
+            3,
+        );
+
+        assert_eq!(loaded.transactions.len(), 3);
+        assert_eq!(loaded.duplicates.len(), 1);
+        match loaded.duplicates[0].reason {
+            DuplicateReason::SameId(ref id) => assert_eq!(id, "2"),
+            ref other => panic!("Unexpected reason {:?}", other),
+        }
+    }
+}
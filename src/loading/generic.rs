@@ -7,12 +7,13 @@
 // except according to those terms.
 
 use {
-    crate::loading::money::Money,
+    crate::loading::{currency::Currency, id::content_hash, money::Money},
     budgetronlib::{error::BResult, fintime::Date},
     serde::{Deserialize, Serialize},
+    std::fmt,
 };
 
-#[derive(Debug, Serialize, Copy, Deserialize, PartialEq, Clone, Eq, PartialOrd, Ord)]
+#[derive(Debug, Serialize, Copy, Deserialize, PartialEq, Clone, Eq, PartialOrd, Ord, Hash)]
 pub enum TransactionType {
     Credit,
     Debit,
@@ -41,7 +42,13 @@ impl TransactionType {
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Transaction {
+    /// The id the source file gave this transaction, if it had one.
     pub uid: Option<String>,
+    /// Budgetron's own id for this transaction. It is assigned when the
+    /// transaction is loaded, is unique, and does not change during
+    /// processing. See `Transaction::uid()`.
+    #[serde(default)]
+    pub id: String,
     pub date: Date,
     pub description: String,
     pub amount: Money,
@@ -55,6 +62,81 @@ pub struct Transaction {
     pub notes: String,
     pub transfer_destination_account: Option<String>,
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    /// The account balance after this transaction, if the source reported
+    /// one.
+    #[serde(default)]
+    pub balance: Option<Money>,
+    /// Every change processors made to this transaction, oldest first.
+    #[serde(default)]
+    pub provenance: Vec<Change>,
+    /// The ids of partial refunds whose amounts were netted against this
+    /// transaction's.
+    #[serde(default)]
+    pub refunded_by: Vec<String>,
+    /// How the amount is split up between categories or owners, if it is.
+    /// See `Transaction::parts()`.
+    #[serde(default)]
+    pub splits: Vec<Split>,
+    /// The id of the transaction this is a monthly portion of, if it was
+    /// amortized.
+    #[serde(default)]
+    pub amortized_from: Option<String>,
+    /// How the transaction is shared between owners, if it is shared by
+    /// more than `person`. See `Transaction::owner_amounts()`.
+    #[serde(default)]
+    pub owner_shares: Vec<OwnerShare>,
+}
+
+/// An owner's share of a transaction.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OwnerShare {
+    pub person: String,
+    /// The owner's percentage of the amount. The shares of a transaction add
+    /// up to 100.
+    pub percent: f64,
+}
+
+impl fmt::Display for OwnerShare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = (self.percent * 100.0).round() / 100.0;
+        write!(f, "{} {}%", self.person, percent)
+    }
+}
+
+/// A part of a transaction with its own category and owner, such as the
+/// groceries on a receipt that also had household goods on it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Split {
+    pub amount: Money,
+    pub category: String,
+    pub person: String,
+    /// Tags for this part only, on top of the transaction's own.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl fmt::Display for Split {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.category)?;
+        if !self.person.is_empty() {
+            write!(f, " @{}", self.person)?;
+        }
+        for tag in &self.tags {
+            write!(f, " #{}", tag)?;
+        }
+        Ok(())
+    }
+}
+
+/// A field a processor set on a transaction, and the rule that made it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Change {
+    pub processor: String,
+    pub rule: String,
+    pub field: String,
+    pub value: String,
 }
 
 pub trait Genericize {
@@ -68,17 +150,133 @@ impl Genericize for Transaction {
 }
 
 impl Transaction {
+    /// The id reports use to refer to this transaction. Transactions that
+    /// were not loaded from a file fall back to a hash of their contents.
     pub fn uid(&self) -> String {
-        format!(
-            "{}{}{}{}",
-            self.date.uid(),
-            self.amount.uid(),
-            match self.transaction_type {
-                TransactionType::Credit => "C",
-                TransactionType::Debit => "D",
-                TransactionType::Transfer => "T",
-            },
-            &self.description,
-        )
+        if self.id.is_empty() {
+            format!("{:016x}", content_hash(self))
+        } else {
+            self.id.clone()
+        }
+    }
+
+    /// The splits scaled by how the amount compares to `from`, so that a
+    /// refund comes out of every split in proportion. Rounding that leaves
+    /// the splits over the amount is taken off the last one.
+    pub fn scaled_splits(&self, from: Money) -> Vec<Split> {
+        let mut splits = self.splits.clone();
+        if from == Money::zero() {
+            return splits;
+        }
+        let ratio = self.amount.to_f64() / from.to_f64();
+        for split in &mut splits {
+            split.amount = split.amount * ratio;
+        }
+        let over = splits.iter().map(|s| s.amount).sum::<Money>() - self.amount;
+        if over > Money::zero() {
+            if let Some(last) = splits.last_mut() {
+                last.amount -= over;
+            }
+        }
+        splits
+    }
+
+    /// The transaction as reports should count it: a copy for each split, with
+    /// the split's amount, category, owner and tags, or just the transaction
+    /// itself if it is not split. Whatever the splits do not add up to stays
+    /// with the transaction's own category and owner, and splits that add up
+    /// to more than the transaction are scaled down to fit.
+    pub fn parts(&self) -> Vec<Transaction> {
+        if self.splits.is_empty() {
+            return vec![self.clone()];
+        }
+        let split_total = self.splits.iter().map(|s| s.amount).sum::<Money>();
+        let splits = if split_total > self.amount {
+            self.scaled_splits(split_total)
+        } else {
+            self.splits.clone()
+        };
+        let part = |amount, category: &str, person: &str, tags: &[String]| {
+            let mut part = Transaction {
+                amount,
+                category: category.to_owned(),
+                person: person.to_owned(),
+                splits: Vec::new(),
+                ..self.clone()
+            };
+            // A part for someone else is theirs alone.
+            if person != self.person {
+                part.owner_shares.clear();
+            }
+            for tag in tags {
+                if !part.tags.contains(tag) {
+                    part.tags.push(tag.clone());
+                }
+            }
+            part
+        };
+
+        let mut parts = splits
+            .iter()
+            .map(|s| part(s.amount, &s.category, &s.person, &s.tags))
+            .collect::<Vec<_>>();
+        let rest = self.amount - splits.iter().map(|s| s.amount).sum::<Money>();
+        if rest != Money::zero() {
+            parts.push(part(rest, &self.category, &self.person, &[]));
+        }
+        parts
+    }
+
+    /// The words of the description that name the merchant, in upper case and
+    /// leaving out store numbers, dates and the like.
+    pub fn merchant(&self) -> String {
+        self.description
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().any(char::is_alphabetic))
+            .map(str::to_uppercase)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// How much of the amount each owner has: their share of it, or all of it
+    /// for `person` if the transaction has no shares. Whatever rounding
+    /// leaves goes to the last owner, so that the amounts always add up.
+    pub fn owner_amounts(&self) -> Vec<(&str, Money)> {
+        if self.owner_shares.is_empty() {
+            return vec![(self.person.as_str(), self.amount)];
+        }
+        let mut left = self.amount;
+        let mut amounts = self
+            .owner_shares
+            .iter()
+            .map(|share| {
+                let amount = self.amount * (share.percent / 100.0);
+                left -= amount;
+                (share.person.as_str(), amount)
+            })
+            .collect::<Vec<_>>();
+        if let Some(last) = amounts.last_mut() {
+            last.1 += left;
+        }
+        amounts
+    }
+
+    /// Whether the owner has all or a share of the transaction.
+    pub fn is_owned_by(&self, owner: &str) -> bool {
+        if self.owner_shares.is_empty() {
+            self.person == owner
+        } else {
+            self.owner_shares.iter().any(|share| share.person == owner)
+        }
+    }
+
+    /// Adds a change to the transaction's provenance.
+    pub fn record(&mut self, processor: &str, rule: String, field: &str, value: String) {
+        self.provenance.push(Change {
+            processor: processor.to_owned(),
+            rule,
+            field: field.to_owned(),
+            value,
+        });
     }
 }
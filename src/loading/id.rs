@@ -0,0 +1,155 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::generic::{Transaction, TransactionType},
+    std::collections::{HashMap, HashSet},
+};
+
+/// 64 bit FNV-1a. Unlike `DefaultHasher` its output is fixed, so ids stay the
+/// same between runs and releases.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn field(&mut self, value: &str) {
+        self.write(value.as_bytes());
+        // Separate fields so that ("ab", "c") and ("a", "bc") differ.
+        self.write(&[0]);
+    }
+}
+
+/// Hashes the parts of a transaction that come straight from its source. Any
+/// id the source provided is used in place of the rest.
+pub fn content_hash(t: &Transaction) -> u64 {
+    let mut hash = Fnv::new();
+    hash.field(&t.account_name);
+    match t.uid {
+        Some(ref uid) => hash.field(uid),
+        None => {
+            hash.field(&t.date.uid());
+            hash.field(&t.amount.uid());
+            hash.field(match t.transaction_type {
+                TransactionType::Credit => "C",
+                TransactionType::Debit => "D",
+                TransactionType::Transfer => "T",
+            });
+            hash.field(&t.original_description);
+        }
+    }
+    hash.0
+}
+
+/// Gives every transaction of a single file that does not have an id one,
+/// made of its content hash and how many identical transactions came before
+/// it in the file. Two identical purchases on the same day therefore get
+/// different ids, and loading the same file again gives the same ids.
+pub fn assign_ids(transactions: &mut [Transaction]) {
+    let mut occurrences = HashMap::new();
+    for t in transactions.iter_mut().filter(|t| t.id.is_empty()) {
+        let hash = content_hash(t);
+        let occurrence = occurrences.entry(hash).or_insert(0);
+        t.id = format!("{:016x}-{}", hash, occurrence);
+        *occurrence += 1;
+    }
+}
+
+/// Makes sure no two transactions share an id, by adding a suffix to any id
+/// that was already seen. Empty ids are left alone, since `uid` falls back to
+/// a hash of the transaction for those.
+pub fn ensure_unique_ids(transactions: &mut [Transaction]) {
+    let mut seen = HashSet::new();
+    for t in transactions {
+        if t.id.is_empty() {
+            continue;
+        }
+        if !seen.contains(&t.id) {
+            seen.insert(t.id.clone());
+            continue;
+        }
+        let mut suffix = 1;
+        while seen.contains(&format!("{}.{}", t.id, suffix)) {
+            suffix += 1;
+        }
+        t.id = format!("{}.{}", t.id, suffix);
+        seen.insert(t.id.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::loading::Money, budgetronlib::fintime::Date};
+
+    fn coffee() -> Transaction {
+        Transaction {
+            date: Date::ymd(2019, 3, 1),
+            description: "COFFEE".to_owned(),
+            original_description: "COFFEE".to_owned(),
+            amount: Money::from_i64(3),
+            transaction_type: TransactionType::Debit,
+            account_name: "Checking".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn identical_transactions_get_distinct_ids() {
+        let mut transactions = vec![coffee(), coffee()];
+        assign_ids(&mut transactions);
+        assert_ne!(transactions[0].id, transactions[1].id);
+
+        let mut again = vec![coffee(), coffee()];
+        assign_ids(&mut again);
+        assert_eq!(transactions[0].id, again[0].id);
+        assert_eq!(transactions[1].id, again[1].id);
+    }
+
+    #[test]
+    fn ids_survive_processing() {
+        let mut transactions = vec![coffee()];
+        assign_ids(&mut transactions);
+        let id = transactions[0].uid();
+        transactions[0].description = "Coffee Shop".to_owned();
+        transactions[0].category = "Food".to_owned();
+        assert_eq!(transactions[0].uid(), id);
+    }
+
+    #[test]
+    fn native_ids_are_used() {
+        let mut with_uid = coffee();
+        with_uid.uid = Some("FIT123".to_owned());
+        let mut other_amount = with_uid.clone();
+        other_amount.amount = Money::from_i64(4);
+        assert_eq!(content_hash(&with_uid), content_hash(&other_amount));
+        assert_ne!(content_hash(&with_uid), content_hash(&coffee()));
+    }
+
+    #[test]
+    fn duplicate_ids_are_renamed() {
+        let mut transactions = vec![coffee(), coffee(), coffee()];
+        for t in &mut transactions {
+            t.id = "a".to_owned();
+        }
+        ensure_unique_ids(&mut transactions);
+        let ids = transactions
+            .iter()
+            .map(|t| t.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["a", "a.1", "a.2"]);
+    }
+}
@@ -0,0 +1,446 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::money::Money,
+    budgetronlib::fintime::{days_in_month, Date},
+    serde::{
+        de::{self, Deserializer, Visitor},
+        Deserialize,
+    },
+    std::{fmt, str::FromStr},
+};
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolPosition {
+    #[default]
+    Before,
+    After,
+}
+
+/// How amounts are written in a file. The defaults match US exports, e.g.
+/// `$1,234.56`; a German bank might use `decimal_separator = ","`,
+/// `grouping_separator = "."`, `currency_symbol = "€"` and
+/// `currency_symbol_position = "after"` for `1.234,56 €`. An empty
+/// `grouping_separator` or `currency_symbol` turns that feature off.
+#[derive(Debug, Deserialize, Clone)]
+pub struct NumberFormat {
+    #[serde(default = "default_decimal_separator")]
+    decimal_separator: char,
+    #[serde(default = "default_grouping_separator")]
+    grouping_separator: String,
+    #[serde(default = "default_currency_symbol")]
+    currency_symbol: String,
+    #[serde(default)]
+    currency_symbol_position: SymbolPosition,
+}
+
+fn default_decimal_separator() -> char {
+    '.'
+}
+
+fn default_grouping_separator() -> String {
+    ",".to_owned()
+}
+
+fn default_currency_symbol() -> String {
+    "$".to_owned()
+}
+
+impl Default for NumberFormat {
+    fn default() -> NumberFormat {
+        NumberFormat {
+            decimal_separator: default_decimal_separator(),
+            grouping_separator: default_grouping_separator(),
+            currency_symbol: default_currency_symbol(),
+            currency_symbol_position: SymbolPosition::default(),
+        }
+    }
+}
+
+impl NumberFormat {
+    fn grouping_separator(&self) -> Option<char> {
+        self.grouping_separator.chars().next()
+    }
+
+    /// Checks that amounts in this format can be read unambiguously.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.grouping_separator.chars().count() > 1 {
+            return Err(format!(
+                "grouping_separator '{}' must be a single character",
+                self.grouping_separator
+            ));
+        }
+        if self.decimal_separator.is_ascii_digit()
+            || self.grouping_separator.chars().any(|g| g.is_ascii_digit())
+        {
+            return Err("separators may not be digits".to_owned());
+        }
+        if self.grouping_separator() == Some(self.decimal_separator) {
+            return Err(format!(
+                "decimal_separator and grouping_separator are both '{}'",
+                self.decimal_separator
+            ));
+        }
+        Ok(())
+    }
+
+    fn strip_symbol<'s>(&self, value: &'s str) -> Result<&'s str, String> {
+        let symbol = self.currency_symbol.as_str();
+        if symbol.is_empty() {
+            return Ok(value);
+        }
+        let at_start = value.starts_with(symbol);
+        let at_end = value.ends_with(symbol);
+        let (stripped, misplaced) = match self.currency_symbol_position {
+            SymbolPosition::Before if at_start => (Some(&value[symbol.len()..]), false),
+            SymbolPosition::Before => (None, at_end),
+            SymbolPosition::After if at_end => (Some(&value[..value.len() - symbol.len()]), false),
+            SymbolPosition::After => (None, at_start),
+        };
+        match stripped {
+            Some(stripped) => Ok(stripped.trim()),
+            None if misplaced => Err(format!(
+                "currency symbol '{}' is on the wrong side of the amount",
+                symbol
+            )),
+            None => Ok(value),
+        }
+    }
+
+    /// Splits the whole part of an amount into its digits, making sure any
+    /// grouping separators sit between groups of exactly three digits so that
+    /// e.g. `1,5` is not silently read as fifteen.
+    fn whole_digits(&self, whole: &str) -> Result<String, String> {
+        let grouping = match self.grouping_separator() {
+            Some(grouping) if whole.contains(grouping) => grouping,
+            _ => return Ok(whole.to_owned()),
+        };
+        let groups = whole.split(grouping).collect::<Vec<_>>();
+        let (first, rest) = groups.split_first().expect("split is never empty");
+        if first.is_empty() || first.len() > 3 || rest.iter().any(|group| group.len() != 3) {
+            return Err(format!(
+                "'{}' is not separating groups of three digits",
+                grouping
+            ));
+        }
+        Ok(groups.concat())
+    }
+
+    pub fn parse_money(&self, s: &str) -> Result<Money, String> {
+        let error = |reason: String| format!("Could not parse money '{}': {}", s, reason);
+        let malformed = || format!("Could not parse money '{}'", s);
+
+        let mut value = s.trim();
+        let mut negative = false;
+        if value.starts_with('(') && value.ends_with(')') && value.len() > 1 {
+            negative = true;
+            value = value[1..value.len() - 1].trim();
+        }
+        let mut signed = negative;
+        let mut take_sign = |value: &str| -> Result<String, String> {
+            let (sign, rest) = match value.chars().next() {
+                Some(c @ '-') | Some(c @ '+') => (Some(c), value[1..].trim_start()),
+                _ => match value.chars().last() {
+                    Some('-') => (Some('-'), value[..value.len() - 1].trim_end()),
+                    _ => (None, value),
+                },
+            };
+            if let Some(sign) = sign {
+                if signed {
+                    return Err(malformed());
+                }
+                signed = true;
+                negative = sign == '-';
+            }
+            Ok(rest.to_owned())
+        };
+        let value = take_sign(value)?;
+        let value = self.strip_symbol(&value).map_err(error)?;
+        let value = take_sign(value)?;
+
+        let mut parts = value.split(self.decimal_separator);
+        let whole = parts.next().unwrap_or("");
+        let fraction = parts.next().unwrap_or("");
+        if parts.next().is_some() {
+            return Err(error(format!(
+                "'{}' appears more than once",
+                self.decimal_separator
+            )));
+        }
+        let whole = self.whole_digits(whole).map_err(error)?;
+        Money::from_digits(negative, &whole, fraction).ok_or_else(malformed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatePart {
+    Year,
+    ShortYear,
+    Month { padded: bool },
+    Day { padded: bool },
+    Literal(char),
+}
+
+impl DatePart {
+    fn field(self) -> Option<char> {
+        match self {
+            DatePart::Year | DatePart::ShortYear => Some('y'),
+            DatePart::Month { .. } => Some('m'),
+            DatePart::Day { .. } => Some('d'),
+            DatePart::Literal(_) => None,
+        }
+    }
+}
+
+/// How dates are written in a file. Either a pattern built from `YYYY`,
+/// `YY`, `MM`, `M`, `DD` and `D` (e.g. `YYYY-MM-DD`, `DD.MM.YYYY` or
+/// `YYYYMMDD`), or a strftime format such as `%d %b %Y`.
+#[derive(Debug, Clone)]
+pub enum DateFormat {
+    Pattern {
+        pattern: String,
+        parts: Vec<DatePart>,
+    },
+    Strftime(String),
+}
+
+impl FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<DateFormat, String> {
+        if pattern.contains('%') {
+            return Ok(DateFormat::Strftime(pattern.to_owned()));
+        }
+
+        let mut parts = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let mut run = 1;
+            while c.is_ascii_alphabetic() && chars.peek() == Some(&c) {
+                chars.next();
+                run += 1;
+            }
+            parts.push(match (c, run) {
+                ('Y', 4) => DatePart::Year,
+                ('Y', 2) => DatePart::ShortYear,
+                ('M', 1) => DatePart::Month { padded: false },
+                ('M', 2) => DatePart::Month { padded: true },
+                ('D', 1) => DatePart::Day { padded: false },
+                ('D', 2) => DatePart::Day { padded: true },
+                (c, _) if c.is_ascii_alphabetic() => {
+                    return Err(format!(
+                        "Unknown date pattern '{}'; use YYYY, YY, MM, M, DD and D",
+                        pattern
+                    ))
+                }
+                (c, _) => DatePart::Literal(c),
+            });
+        }
+
+        let count = |field| parts.iter().filter(|p| p.field() == Some(field)).count();
+        if count('y') != 1 || count('m') != 1 || count('d') != 1 {
+            return Err(format!(
+                "Date pattern '{}' needs exactly one year, month and day",
+                pattern
+            ));
+        }
+        // `M` and `D` take one or two digits, so they need something after
+        // them to say where they end.
+        for (i, part) in parts.iter().enumerate() {
+            match (*part, parts.get(i + 1)) {
+                (DatePart::Month { padded: false }, Some(DatePart::Literal(_)))
+                | (DatePart::Day { padded: false }, Some(DatePart::Literal(_)))
+                | (DatePart::Month { padded: false }, None)
+                | (DatePart::Day { padded: false }, None) => {}
+                (DatePart::Month { padded: false }, _) | (DatePart::Day { padded: false }, _) => {
+                    return Err(format!(
+                        "Date pattern '{}' is ambiguous; use MM and DD without separators",
+                        pattern
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(DateFormat::Pattern {
+            pattern: pattern.to_owned(),
+            parts,
+        })
+    }
+}
+
+/// The year a two digit year stands for: 70 to 99 are 19xx and the rest
+/// are 20xx.
+pub fn full_year(short: i32) -> i32 {
+    if short < 70 {
+        2000 + short
+    } else {
+        1900 + short
+    }
+}
+
+fn take_digits(value: &str, pos: &mut usize, min: usize, max: usize) -> Option<i32> {
+    let len = value[*pos..]
+        .bytes()
+        .take(max)
+        .take_while(u8::is_ascii_digit)
+        .count();
+    if len < min {
+        return None;
+    }
+    let digits = value[*pos..*pos + len].parse().ok()?;
+    *pos += len;
+    Some(digits)
+}
+
+impl DateFormat {
+    pub fn parse(&self, value: &str) -> Result<Date, String> {
+        let (pattern, parts) = match *self {
+            DateFormat::Strftime(ref format) => return Date::parse_with_format(value, format),
+            DateFormat::Pattern {
+                ref pattern,
+                ref parts,
+            } => (pattern, parts),
+        };
+        let value = value.trim();
+        let error = || format!("'{}' does not match the date format '{}'", value, pattern);
+
+        let (mut y, mut m, mut d) = (0, 0, 0);
+        let mut pos = 0;
+        for part in parts {
+            match *part {
+                DatePart::Year => y = take_digits(value, &mut pos, 4, 4).ok_or_else(error)?,
+                DatePart::ShortYear => {
+                    y = full_year(take_digits(value, &mut pos, 2, 2).ok_or_else(error)?);
+                }
+                DatePart::Month { padded } => {
+                    m = take_digits(value, &mut pos, if padded { 2 } else { 1 }, 2)
+                        .ok_or_else(error)?
+                }
+                DatePart::Day { padded } => {
+                    d = take_digits(value, &mut pos, if padded { 2 } else { 1 }, 2)
+                        .ok_or_else(error)?
+                }
+                DatePart::Literal(c) => {
+                    if !value[pos..].starts_with(c) {
+                        return Err(error());
+                    }
+                    pos += c.len_utf8();
+                }
+            }
+        }
+        if pos != value.len() {
+            return Err(error());
+        }
+        if !(1..=12).contains(&m)
+            || d < 1
+            || i64::from(d) > days_in_month(i64::from(m), i64::from(y))
+        {
+            return Err(format!("'{}' is not a valid date", value));
+        }
+        Ok(Date::ymd(y, m, d))
+    }
+}
+
+struct DateFormatVisitor;
+impl<'de> Visitor<'de> for DateFormatVisitor {
+    type Value = DateFormat;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a date pattern such as YYYY-MM-DD")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<DateFormat, E>
+    where
+        E: de::Error,
+    {
+        value.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for DateFormat {
+    fn deserialize<D>(deserializer: D) -> Result<DateFormat, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(DateFormatVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn european() -> NumberFormat {
+        NumberFormat {
+            decimal_separator: ',',
+            grouping_separator: ".".to_owned(),
+            currency_symbol: "€".to_owned(),
+            currency_symbol_position: SymbolPosition::After,
+        }
+    }
+
+    #[test]
+    fn european_amounts() {
+        let format = european();
+        assert_eq!(format.parse_money("1.234,56"), "1234.56".parse());
+        assert_eq!(format.parse_money("-1.234,56 €"), "-1234.56".parse());
+        assert_eq!(format.parse_money("12,5-"), "-12.50".parse());
+        assert_eq!(format.parse_money("1.234"), "1234".parse());
+        for bad in &["12.50", "1,2,3", "€ 5,00", "--5", "1.23,4"] {
+            assert!(format.parse_money(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn grouping_must_be_groups_of_three() {
+        let format = NumberFormat::default();
+        assert_eq!(format.parse_money("$1,234,567.89"), "1234567.89".parse());
+        assert!(format.parse_money("1,5").is_err());
+        assert!(format.parse_money("12,34.00").is_err());
+        assert!(format.parse_money("5 $").is_err());
+    }
+
+    #[test]
+    fn invalid_formats() {
+        let mut format = european();
+        format.grouping_separator = ",".to_owned();
+        assert!(format.validate().is_err());
+        assert!(european().validate().is_ok());
+    }
+
+    #[test]
+    fn date_patterns() {
+        let parse = |pattern: &str, value| pattern.parse::<DateFormat>().unwrap().parse(value);
+        assert_eq!(
+            parse("YYYY-MM-DD", "2019-12-31"),
+            Ok(Date::ymd(2019, 12, 31))
+        );
+        assert_eq!(
+            parse("DD.MM.YYYY", "31.12.2019"),
+            Ok(Date::ymd(2019, 12, 31))
+        );
+        assert_eq!(parse("YYYYMMDD", "20191231"), Ok(Date::ymd(2019, 12, 31)));
+        assert_eq!(parse("D.M.YY", "1.2.19"), Ok(Date::ymd(2019, 2, 1)));
+        assert_eq!(parse("%d %b %Y", "01 Feb 2019"), Ok(Date::ymd(2019, 2, 1)));
+        assert!(parse("DD.MM.YYYY", "12/31/2019").is_err());
+        assert!(parse("DD.MM.YYYY", "31.12.19").is_err());
+        assert!(parse("YYYY-MM-DD", "2019-02-30").is_err());
+        assert!(parse("YYYYMMDD", "2019123").is_err());
+
+        for bad in &["YYYYMD", "DD.MM", "YYY-MM-DD", "DD.MM.YYYY HH"] {
+            assert!(
+                bad.parse::<DateFormat>().is_err(),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+}
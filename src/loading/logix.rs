@@ -31,6 +31,7 @@ impl Genericize for LogixExport {
     fn genericize(self) -> BResult<Transaction> {
         Ok(Transaction {
             uid: None,
+            id: "".to_owned(),
             date: self.date,
             person: "".to_owned(),
             description: self.description.clone(),
@@ -48,6 +49,13 @@ impl Genericize for LogixExport {
             notes: self.notes,
             transfer_destination_account: None,
             tags: vec![],
+            currency: None,
+            balance: Some(self.balance),
+            provenance: vec![],
+            refunded_by: vec![],
+            splits: vec![],
+            amortized_from: None,
+            owner_shares: vec![],
         })
     }
 }
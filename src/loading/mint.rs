@@ -51,6 +51,7 @@ impl Genericize for MintExport {
     fn genericize(self) -> BResult<Transaction> {
         Ok(Transaction {
             uid: None,
+            id: "".to_owned(),
             date: self.date,
             person: "".to_owned(),
             description: self.description,
@@ -64,6 +65,13 @@ impl Genericize for MintExport {
             notes: self.notes,
             transfer_destination_account: None,
             tags: vec![],
+            currency: None,
+            balance: None,
+            provenance: vec![],
+            refunded_by: vec![],
+            splits: vec![],
+            amortized_from: None,
+            owner_shares: vec![],
         })
     }
 }
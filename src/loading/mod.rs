@@ -7,14 +7,25 @@
 // except according to those terms.
 
 pub mod alliant;
+mod config;
+mod currency;
+mod dedupe;
 mod generic;
+mod id;
+mod locale;
 pub mod logix;
 pub mod mint;
 mod money;
+pub mod ofx;
+pub mod qif;
 mod util;
 
 pub use self::{
-    generic::{Transaction, TransactionType},
+    config::{ConfiguredImporters, ImporterConfig},
+    currency::Currency,
+    dedupe::{Duplicate, DuplicateReason, LoadedTransactions},
+    generic::{Change, OwnerShare, Split, Transaction, TransactionType},
+    id::ensure_unique_ids,
     money::Money,
-    util::load_from_files,
+    util::{load_from_files, InputFile},
 };
@@ -7,15 +7,15 @@
 // except according to those terms.
 
 use {
+    crate::loading::locale::NumberFormat,
     serde::{
         de::{self, Deserialize, Deserializer, Visitor},
         ser::{Serialize, Serializer},
     },
-    serde_json,
     std::{convert::TryInto, fmt, iter, ops, str::FromStr},
 };
 
-#[derive(Debug, Copy, PartialEq, Clone, Eq, PartialOrd, Ord, Default)]
+#[derive(Debug, Copy, PartialEq, Clone, Eq, PartialOrd, Ord, Default, Hash)]
 pub struct Money(i64);
 
 impl ops::Sub<Money> for Money {
@@ -89,20 +89,25 @@ impl ops::Mul<Money> for Money {
     }
 }
 
+/// Scales by a ratio (e.g. a budget proportion). This is the only place money
+/// goes through floating point, and the result is rounded to the nearest
+/// representable amount.
 impl ops::Mul<f64> for Money {
     type Output = Money;
     fn mul(self, other: f64) -> Money {
-        Money::from_f64(self.to_f64() * other)
+        Money(((self.0 as f64) * other).round() as i64)
     }
 }
 
+/// Multiplies by a count (e.g. a number of periods). Like scaling by a ratio,
+/// the result saturates instead of overflowing.
 macro_rules! impl_mul {
     ($($i:ident),*) => {
         $(
             impl ops::Mul<$i> for Money {
                 type Output = Money;
                 fn mul(self, other:$i) -> Money {
-                    self * f64::from(other)
+                    Money(self.0.saturating_mul(i64::from(other)))
                 }
             }
         )*
@@ -125,17 +130,91 @@ impl<'a> ops::Div<Money> for &'a Money {
     }
 }
 
+/// Money is stored as a fixed point number with this many implied decimals.
+const DECIMALS: usize = 4;
+const SCALE: i64 = 10_000;
+
 impl Money {
     pub fn to_f64(self) -> f64 {
-        (self.0 as f64) / 10_000.0
+        (self.0 as f64) / SCALE as f64
     }
 
     pub fn from_f64(v: f64) -> Money {
-        Money((v * 100.0) as i64 * 100)
+        Money((v * SCALE as f64).round() as i64)
+    }
+
+    /// Parses an amount written the US way, such as `1,234.56`, `-$12.00` or
+    /// `($5.25)`, without going through floating point. Digits past the
+    /// fourth decimal place are rounded half away from zero.
+    pub fn parse_str(s: &str) -> Result<Money, String> {
+        NumberFormat::default().parse_money(s)
+    }
+
+    /// Builds an amount from the ASCII digits on either side of the decimal
+    /// point, rounding half away from zero past four decimals. Returns `None`
+    /// if the digits are malformed or the amount does not fit.
+    pub(crate) fn from_digits(negative: bool, whole: &str, fraction: &str) -> Option<Money> {
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole.bytes().all(|c| c.is_ascii_digit())
+            || !fraction.bytes().all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let whole = if whole.is_empty() {
+            0
+        } else {
+            whole.parse::<i64>().ok()?.checked_mul(SCALE)?
+        };
+        let mut fractional = fraction
+            .bytes()
+            .chain(iter::repeat(b'0'))
+            .take(DECIMALS)
+            .fold(0, |acc, digit| acc * 10 + i64::from(digit - b'0'));
+        if fraction.len() > DECIMALS && fraction.as_bytes()[DECIMALS] >= b'5' {
+            fractional += 1;
+        }
+
+        let value = whole.checked_add(fractional)?;
+        Some(Money(if negative { -value } else { value }))
+    }
+
+    /// The amount rounded (half away from zero) to whole cents.
+    fn cents(self) -> i64 {
+        let per_cent = SCALE / 100;
+        let remainder = self.0 % per_cent;
+        let cents = self.0 / per_cent;
+        if remainder * 2 >= per_cent {
+            cents + 1
+        } else if remainder * 2 <= -per_cent {
+            cents - 1
+        } else {
+            cents
+        }
+    }
+
+    /// Divides the amount into `n` (at least one) portions of whole cents that
+    /// add up to it exactly. The first portions are a cent larger when it does
+    /// not divide evenly, and the first one also keeps anything smaller than a
+    /// cent.
+    pub fn portions(self, n: usize) -> Vec<Money> {
+        let per_cent = SCALE / 100;
+        let cents = self.0 / per_cent;
+        let (each, extra) = (cents / n as i64, (cents.abs() % n as i64) as usize);
+        let mut portions = (0..n)
+            .map(|i| {
+                let larger = if i < extra { cents.signum() } else { 0 };
+                Money((each + larger) * per_cent)
+            })
+            .collect::<Vec<_>>();
+        if let Some(first) = portions.first_mut() {
+            first.0 += self.0 - cents * per_cent;
+        }
+        portions
     }
 
     pub fn from_i64(v: i64) -> Money {
-        Money(v * 10_000)
+        Money(v * SCALE)
     }
 
     pub fn abs(self) -> Money {
@@ -159,8 +238,7 @@ impl FromStr for Money {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Money, String> {
-        serde_json::from_str(&format!("\"{}\"", s))
-            .map_err(|_| format!("Unable to parse money {}", s))
+        Money::parse_str(s)
     }
 }
 
@@ -206,15 +284,7 @@ impl<'de> Visitor<'de> for MoneyVisitor {
     where
         E: de::Error,
     {
-        let negative = v.starts_with('(') && v.ends_with(')');
-        let v = if negative { &v[1..v.len() - 1] } else { v };
-        let v = v.replace('$', "").replace(',', "");
-        let mut parsed: f64 = v.parse().map_err(|_| E::custom("Could not parse money"))?;
-        if negative {
-            parsed = -parsed;
-        }
-
-        self.visit_f64(parsed)
+        Money::parse_str(v).map_err(E::custom)
     }
 }
 
@@ -232,7 +302,7 @@ impl Serialize for Money {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&format!("{:.02}", self.to_f64()))
+        serializer.serialize_str(&self.to_string())
     }
 }
 
@@ -262,6 +332,14 @@ mod tests {
         assert_eq!(mon, Money(28));
     }
 
+    #[test]
+    fn multiplying_saturates() {
+        assert_eq!(Money(10) * 3i32, Money(30));
+        // The largest and smallest amounts there are.
+        assert_eq!(Money(1 << 62) * 3i32, Money((1 << 62) - 1 + (1 << 62)));
+        assert_eq!(Money(-(1 << 62)) * 3u32, Money(-(1 << 62) * 2));
+    }
+
     #[test]
     fn iter_operations() {
         let monies = vec![Money(10), Money(5)];
@@ -300,10 +378,95 @@ mod tests {
         "100.0" => 100.0,
         "0" => 0.0
     );
+
+    macro_rules! test_exact {
+        ($name:ident, $($s:expr => $o:expr),+) => {
+            #[test]
+            fn $name() {
+                $(
+                    assert_eq!($s.parse::<Money>(), Ok(Money($o)), "parsing {}", $s);
+                 )+
+            }
+        }
+    }
+
+    test_exact!(
+        parse_exact_decimals,
+        "0.29" => 2_900,
+        "1.005" => 10_050,
+        "-0.07" => -700,
+        ".5" => 5_000,
+        "12." => 120_000,
+        "(1,234.56)" => -12_345_600,
+        "+$3.10" => 31_000
+    );
+
+    test_exact!(
+        parse_rounds_past_four_decimals,
+        "1.00005" => 10_001,
+        "1.00004999" => 10_000,
+        "-1.00005" => -10_001,
+        "0.99999" => 10_000
+    );
+
+    test_exact!(
+        parse_large_values,
+        "922,337,203,685,477.5807" => 9_223_372_036_854_775_807,
+        "1234567890123.45" => 12_345_678_901_234_500
+    );
+
+    #[test]
+    fn parse_errors() {
+        for bad in &["", "$", "1.2.3", "12a", "1e5", "-", "922337203685478"] {
+            assert!(bad.parse::<Money>().is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn display_rounds_to_cents() {
+        assert_eq!(Money(2_900).to_string(), "0.29");
+        assert_eq!(Money(10_050).to_string(), "1.01");
+        assert_eq!(Money(10_049).to_string(), "1.00");
+        assert_eq!(Money(-10_050).to_string(), "-1.01");
+        assert_eq!(Money(-400).to_string(), "-0.04");
+        assert_eq!(Money(-49).to_string(), "0.00");
+        assert_eq!(
+            Money(12_345_678_901_234_500).to_string(),
+            "1234567890123.45"
+        );
+    }
+
+    #[test]
+    fn sums_are_exact() {
+        let total: Money = (0..1000).map(|_| "0.29".parse::<Money>().unwrap()).sum();
+        assert_eq!(total, Money::from_i64(290));
+        assert_eq!(Money::from_f64(0.29), Money(2_900));
+        assert_eq!(Money(2_900) * 3, Money(8_700));
+        assert_eq!(Money(10_000) * 0.5, Money(5_000));
+    }
+
+    #[test]
+    fn portions_add_up() {
+        let premium: Money = "1000.00".parse().unwrap();
+        let portions = premium.portions(12);
+        assert_eq!(portions[0], "83.34".parse().unwrap());
+        assert_eq!(portions[3], "83.34".parse().unwrap());
+        assert_eq!(portions[4], "83.33".parse().unwrap());
+        assert_eq!(portions.iter().sum::<Money>(), premium);
+
+        let converted = Money(-123_456);
+        let portions = converted.portions(5);
+        assert_eq!(portions[1], Money(-24_700));
+        assert_eq!(portions[4], Money(-24_600));
+        assert_eq!(portions.iter().sum::<Money>(), converted);
+    }
 }
 
 impl fmt::Display for Money {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:.02}", self.to_f64())
+        let cents = self.cents();
+        let sign = if cents < 0 { "-" } else { "" };
+        let cents = cents.abs();
+        write!(f, "{}{}.{:02}", sign, cents / 100, cents % 100)
     }
 }
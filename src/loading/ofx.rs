@@ -0,0 +1,466 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::{
+        currency::Currency,
+        generic::{Genericize, Transaction, TransactionType},
+        money::Money,
+    },
+    budgetronlib::{
+        error::{BResult, BudgetError},
+        fintime::{days_in_month, Date},
+    },
+    std::{io::Read, str::FromStr},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OfxTransactionType {
+    Credit,
+    Debit,
+    Int,
+    Div,
+    Fee,
+    SrvChg,
+    Dep,
+    Atm,
+    Pos,
+    Xfer,
+    Check,
+    Payment,
+    Cash,
+    DirectDep,
+    DirectDebit,
+    RepeatPmt,
+    Hold,
+    Other,
+}
+
+impl FromStr for OfxTransactionType {
+    type Err = BudgetError;
+
+    fn from_str(s: &str) -> BResult<OfxTransactionType> {
+        use self::OfxTransactionType::*;
+        Ok(match s {
+            "CREDIT" => Credit,
+            "DEBIT" => Debit,
+            "INT" => Int,
+            "DIV" => Div,
+            "FEE" => Fee,
+            "SRVCHG" => SrvChg,
+            "DEP" => Dep,
+            "ATM" => Atm,
+            "POS" => Pos,
+            "XFER" => Xfer,
+            "CHECK" => Check,
+            "PAYMENT" => Payment,
+            "CASH" => Cash,
+            "DIRECTDEP" => DirectDep,
+            "DIRECTDEBIT" => DirectDebit,
+            "REPEATPMT" => RepeatPmt,
+            "HOLD" => Hold,
+            "OTHER" => Other,
+            other => {
+                return Err(BudgetError::ParseError(format!(
+                    "Unknown OFX TRNTYPE '{}'",
+                    other
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct OfxTransaction {
+    fitid: String,
+    trntype: OfxTransactionType,
+    posted: Date,
+    amount: Money,
+    name: String,
+    memo: String,
+    account_id: String,
+    destination_account_id: Option<String>,
+    currency: Option<Currency>,
+}
+
+impl OfxTransaction {
+    fn transaction_type(&self) -> TransactionType {
+        use self::OfxTransactionType::*;
+        match self.trntype {
+            Credit | Int | Div | Dep | DirectDep => TransactionType::Credit,
+            Debit | Fee | SrvChg | Atm | Pos | Check | Payment | Cash | DirectDebit | RepeatPmt => {
+                TransactionType::Debit
+            }
+            Xfer if self.destination_account_id.is_some() => TransactionType::Transfer,
+            Xfer | Hold | Other => {
+                if self.amount.is_negative() {
+                    TransactionType::Debit
+                } else {
+                    TransactionType::Credit
+                }
+            }
+        }
+    }
+}
+
+impl Genericize for OfxTransaction {
+    fn genericize(self) -> BResult<Transaction> {
+        let transaction_type = self.transaction_type();
+        let description = if self.name.is_empty() {
+            self.memo.clone()
+        } else {
+            self.name
+        };
+        Ok(Transaction {
+            uid: Some(self.fitid),
+            id: "".to_owned(),
+            date: self.posted,
+            person: "".to_owned(),
+            description: description.clone(),
+            original_description: description,
+            amount: self.amount.abs(),
+            transaction_type,
+            category: "".to_owned(),
+            original_category: "".to_owned(),
+            account_name: self.account_id,
+            labels: self.memo,
+            notes: "".to_owned(),
+            transfer_destination_account: if transaction_type.is_transfer() {
+                self.destination_account_id
+            } else {
+                None
+            },
+            tags: vec![],
+            currency: self.currency,
+            balance: None,
+            provenance: vec![],
+            refunded_by: vec![],
+            splits: vec![],
+            amortized_from: None,
+            owner_shares: vec![],
+        })
+    }
+}
+
+enum Token<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    Text(&'a str),
+}
+
+/// Splits the body of an OFX document into tags and text. This handles both
+/// the SGML (1.x) and XML (2.x) flavors, since the only difference that
+/// matters here is whether leaf elements have closing tags. Each token is
+/// paired with its byte offset into `body`.
+fn tokenize(body: &str) -> BResult<Vec<(usize, Token<'_>)>> {
+    let mut tokens = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let offset = body.len() - rest.len();
+        match rest.find('<') {
+            Some(0) => {
+                let end = rest.find('>').ok_or_else(|| {
+                    BudgetError::ParseError("Unterminated tag in OFX body".to_owned())
+                })?;
+                let tag = rest[1..end].trim();
+                match tag.chars().next() {
+                    Some('/') => tokens.push((offset, Token::Close(tag[1..].trim()))),
+                    Some('?') | Some('!') => {}
+                    _ => tokens.push((offset, Token::Open(tag.trim_end_matches('/')))),
+                }
+                rest = &rest[end + 1..];
+            }
+            Some(start) => {
+                tokens.push((offset, Token::Text(&rest[..start])));
+                rest = &rest[start..];
+            }
+            None => {
+                tokens.push((offset, Token::Text(rest)));
+                rest = "";
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn unescape(text: &str) -> String {
+    text.trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn parse_date(value: &str) -> BResult<Date> {
+    let error = || BudgetError::ParseError(format!("Bad OFX date '{}'", value));
+    if value.len() < 8 || !value.is_char_boundary(8) {
+        return Err(error());
+    }
+    let y: i32 = value[0..4].parse().map_err(|_| error())?;
+    let m: i32 = value[4..6].parse().map_err(|_| error())?;
+    let d: i32 = value[6..8].parse().map_err(|_| error())?;
+    if !(1..=12).contains(&m) || d < 1 || i64::from(d) > days_in_month(i64::from(m), i64::from(y)) {
+        return Err(error());
+    }
+    Ok(Date::ymd(y, m, d))
+}
+
+#[derive(Default)]
+struct PartialTransaction {
+    fitid: Option<String>,
+    trntype: Option<OfxTransactionType>,
+    posted: Option<Date>,
+    amount: Option<Money>,
+    name: String,
+    memo: String,
+    destination_account_id: Option<String>,
+}
+
+impl PartialTransaction {
+    fn set(&mut self, tag: &str, value: String) -> BResult<()> {
+        match tag {
+            "FITID" => self.fitid = Some(value),
+            "TRNTYPE" => self.trntype = Some(value.parse()?),
+            "DTPOSTED" => self.posted = Some(parse_date(&value)?),
+            "TRNAMT" => self.amount = Some(value.parse().map_err(BudgetError::ParseError)?),
+            "NAME" => self.name = value,
+            "MEMO" => self.memo = value,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn build(
+        self,
+        account_id: Option<&String>,
+        currency: Option<Currency>,
+    ) -> BResult<OfxTransaction> {
+        macro_rules! required {
+            ($field:ident, $tag:expr) => {
+                self.$field.ok_or_else(|| {
+                    BudgetError::ParseError(format!("STMTTRN is missing <{}>", $tag))
+                })?
+            };
+        }
+        Ok(OfxTransaction {
+            fitid: required!(fitid, "FITID"),
+            trntype: required!(trntype, "TRNTYPE"),
+            posted: required!(posted, "DTPOSTED"),
+            amount: required!(amount, "TRNAMT"),
+            name: self.name,
+            memo: self.memo,
+            account_id: account_id.cloned().ok_or_else(|| {
+                BudgetError::ParseError("STMTTRN found before any account id".to_owned())
+            })?,
+            destination_account_id: self.destination_account_id,
+            currency,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AccountContext {
+    From,
+    To,
+}
+
+pub fn parse<R: Read>(reader: &mut R) -> BResult<Vec<OfxTransaction>> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+
+    let start = match contents.find("<OFX>") {
+        Some(start) => start,
+        None => return Err(BudgetError::ParseError("No <OFX> element found".to_owned())),
+    };
+    let body = &contents[start..];
+    let at_offset = |error: BudgetError, offset: usize, field: &str| match error {
+        BudgetError::ParseError(message) => BudgetError::FieldError {
+            line: contents[..start + offset].matches('\n').count() as u64 + 1,
+            column: None,
+            field: Some(field.to_owned()),
+            message,
+        },
+        other => other,
+    };
+
+    let mut transactions = Vec::new();
+    let mut account_id = None;
+    let mut currency = None;
+    let mut account_context = None;
+    let mut current: Option<PartialTransaction> = None;
+    let mut open_tag = None;
+
+    for (offset, token) in tokenize(body)? {
+        match token {
+            Token::Open(tag) => {
+                match tag {
+                    "STMTTRN" => current = Some(PartialTransaction::default()),
+                    "BANKACCTFROM" | "CCACCTFROM" => account_context = Some(AccountContext::From),
+                    "BANKACCTTO" | "CCACCTTO" => account_context = Some(AccountContext::To),
+                    _ => {}
+                }
+                open_tag = Some(tag);
+            }
+            Token::Close(tag) => {
+                match tag {
+                    "STMTTRN" => {
+                        if let Some(partial) = current.take() {
+                            transactions.push(
+                                partial
+                                    .build(account_id.as_ref(), currency)
+                                    .map_err(|e| at_offset(e, offset, tag))?,
+                            );
+                        }
+                    }
+                    "BANKACCTFROM" | "CCACCTFROM" | "BANKACCTTO" | "CCACCTTO" => {
+                        account_context = None
+                    }
+                    _ => {}
+                }
+                open_tag = None;
+            }
+            Token::Text(text) => {
+                if text.trim().is_empty() {
+                    continue;
+                }
+                if let Some(tag) = open_tag.take() {
+                    let value = unescape(text);
+                    match (tag, account_context) {
+                        ("ACCTID", Some(AccountContext::From)) => account_id = Some(value),
+                        ("CURDEF", _) => {
+                            currency = Some(
+                                value
+                                    .parse()
+                                    .map_err(BudgetError::ParseError)
+                                    .map_err(|e| at_offset(e, offset, tag))?,
+                            )
+                        }
+                        ("ACCTID", Some(AccountContext::To)) => {
+                            if let Some(ref mut partial) = current {
+                                partial.destination_account_id = Some(value);
+                            }
+                        }
+                        _ => {
+                            if let Some(ref mut partial) = current {
+                                partial
+                                    .set(tag, value)
+                                    .map_err(|e| at_offset(e, offset, tag))?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> Vec<Transaction> {
+        parse(&mut contents.as_bytes())
+            .expect("Unable to parse OFX")
+            .into_iter()
+            .map(|t| t.genericize().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parse_sgml() {
+        let transactions = load(
+            "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>USD
+<BANKACCTFROM><BANKID>123<ACCTID>0001234<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<DTSTART>20190101
+<STMTTRN>
+<TRNTYPE>POS
+<DTPOSTED>20190105120000.000[-8:PST]
+<TRNAMT>-12.29
+<FITID>A1
+<NAME>COFFEE &amp; CO
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>DIRECTDEP
+<DTPOSTED>20190115
+<TRNAMT>1000.00
+<FITID>A2
+<MEMO>PAYROLL
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>",
+        );
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].uid, Some("A1".to_owned()));
+        assert_eq!(transactions[0].date, Date::ymd(2019, 1, 5));
+        assert_eq!(transactions[0].description, "COFFEE & CO");
+        assert_eq!(transactions[0].amount, "12.29".parse().unwrap());
+        assert_eq!(transactions[0].transaction_type, TransactionType::Debit);
+        assert_eq!(transactions[0].account_name, "0001234");
+        assert_eq!(transactions[0].currency, Some("USD".parse().unwrap()));
+        assert_eq!(transactions[1].description, "PAYROLL");
+        assert_eq!(transactions[1].transaction_type, TransactionType::Credit);
+    }
+
+    #[test]
+    fn parse_xml() {
+        let transactions = load(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="211" SECURITY="NONE"?>
+<OFX>
+  <CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+    <CCACCTFROM><ACCTID>4444</ACCTID></CCACCTFROM>
+    <BANKTRANLIST>
+      <STMTTRN>
+        <TRNTYPE>XFER</TRNTYPE>
+        <DTPOSTED>20190220</DTPOSTED>
+        <TRNAMT>-50.00</TRNAMT>
+        <FITID>X1</FITID>
+        <NAME>TRANSFER</NAME>
+        <BANKACCTTO><BANKID>1</BANKID><ACCTID>9999</ACCTID></BANKACCTTO>
+      </STMTTRN>
+      <STMTTRN>
+        <TRNTYPE>OTHER</TRNTYPE>
+        <DTPOSTED>20190221</DTPOSTED>
+        <TRNAMT>25.00</TRNAMT>
+        <FITID>X2</FITID>
+        <PAYEE><NAME>REFUND</NAME></PAYEE>
+      </STMTTRN>
+    </BANKTRANLIST>
+  </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>"#,
+        );
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].account_name, "4444");
+        assert_eq!(transactions[0].currency, None);
+        assert_eq!(transactions[0].transaction_type, TransactionType::Transfer);
+        assert_eq!(
+            transactions[0].transfer_destination_account,
+            Some("9999".to_owned())
+        );
+        assert_eq!(transactions[1].description, "REFUND");
+        assert_eq!(transactions[1].transaction_type, TransactionType::Credit);
+    }
+
+    #[test]
+    fn rejects_non_ofx() {
+        assert!(parse(&mut "date,description,amount\n".as_bytes()).is_err());
+        assert!(parse(&mut "<OFX><STMTTRN><TRNTYPE>DEBIT</STMTTRN>".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,484 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::{
+        generic::{Genericize, Transaction, TransactionType},
+        locale::full_year,
+        money::Money,
+    },
+    budgetronlib::{
+        error::{BResult, BudgetError},
+        fintime::{days_in_month, Date},
+    },
+    log::warn,
+    std::io::Read,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearedStatus {
+    Uncleared,
+    Cleared,
+    Reconciled,
+}
+
+impl ClearedStatus {
+    fn parse(value: &str) -> BResult<ClearedStatus> {
+        match value.trim() {
+            "" => Ok(ClearedStatus::Uncleared),
+            "*" | "c" => Ok(ClearedStatus::Cleared),
+            "X" | "R" => Ok(ClearedStatus::Reconciled),
+            other => Err(BudgetError::ParseError(format!(
+                "Unknown QIF cleared status '{}'",
+                other
+            ))),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ClearedStatus::Uncleared => "",
+            ClearedStatus::Cleared => "Cleared",
+            ClearedStatus::Reconciled => "Reconciled",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QifTransaction {
+    date: Date,
+    amount: Money,
+    payee: String,
+    memo: String,
+    category: String,
+    cleared: ClearedStatus,
+    account_name: String,
+}
+
+impl Genericize for QifTransaction {
+    fn genericize(self) -> BResult<Transaction> {
+        Ok(Transaction {
+            uid: None,
+            id: "".to_owned(),
+            date: self.date,
+            person: "".to_owned(),
+            description: self.payee.clone(),
+            original_description: self.payee,
+            amount: self.amount.abs(),
+            transaction_type: if self.amount.is_negative() {
+                TransactionType::Debit
+            } else {
+                TransactionType::Credit
+            },
+            category: self.category.clone(),
+            original_category: self.category,
+            account_name: self.account_name,
+            labels: self.cleared.label().to_owned(),
+            notes: self.memo,
+            transfer_destination_account: None,
+            tags: vec![],
+            currency: None,
+            balance: None,
+            provenance: vec![],
+            refunded_by: vec![],
+            splits: vec![],
+            amortized_from: None,
+            owner_shares: vec![],
+        })
+    }
+}
+
+/// Parses the date formats written by the various versions of Quicken and
+/// GnuCash: `12/25/2019`, `12/25/19`, `12/25'19`, ` 1/ 5'20` and
+/// `2019-12-25`. An apostrophe before a two digit year means 20xx.
+fn parse_date(value: &str) -> BResult<Date> {
+    let error = || BudgetError::ParseError(format!("Bad QIF date '{}'", value));
+    let value = value.replace(' ', "");
+    let post_2000 = value.contains('\'');
+    let parts = value
+        .split(&['/', '\'', '-', '.'][..])
+        .map(|part| part.parse::<i32>().map_err(|_| error()))
+        .collect::<BResult<Vec<_>>>()?;
+    if parts.len() != 3 {
+        return Err(error());
+    }
+    let (y, m, d) = if value.contains('-') && parts[0] > 31 {
+        (parts[0], parts[1], parts[2])
+    } else {
+        let y = match parts[2] {
+            y if y >= 100 => y,
+            y if post_2000 => 2000 + y,
+            y => full_year(y),
+        };
+        (y, parts[0], parts[1])
+    };
+    if !(1..=12).contains(&m) || d < 1 || i64::from(d) > days_in_month(i64::from(m), i64::from(y)) {
+        return Err(error());
+    }
+    Ok(Date::ymd(y, m, d))
+}
+
+fn parse_amount(value: &str) -> BResult<Money> {
+    value.trim().parse().map_err(BudgetError::ParseError)
+}
+
+#[derive(Default)]
+struct Split {
+    category: String,
+    memo: String,
+    amount: Option<Money>,
+}
+
+#[derive(Default)]
+struct PartialTransaction {
+    date: Option<Date>,
+    amount: Option<Money>,
+    payee: String,
+    memo: String,
+    category: String,
+    cleared: String,
+    splits: Vec<Split>,
+}
+
+impl PartialTransaction {
+    fn set(&mut self, code: char, value: &str) -> BResult<()> {
+        match code {
+            'D' => self.date = Some(parse_date(value)?),
+            'T' => self.amount = Some(parse_amount(value)?),
+            'P' => self.payee = value.to_owned(),
+            'M' => self.memo = value.to_owned(),
+            'L' => self.category = value.to_owned(),
+            'C' => self.cleared = value.to_owned(),
+            'S' => self.splits.push(Split {
+                category: value.to_owned(),
+                ..Default::default()
+            }),
+            'E' | '$' => {
+                let split = self.splits.last_mut().ok_or_else(|| {
+                    BudgetError::ParseError(format!("Split field '{}' before any 'S' line", code))
+                })?;
+                if code == 'E' {
+                    split.memo = value.to_owned();
+                } else {
+                    split.amount = Some(parse_amount(value)?);
+                }
+            }
+            // U (amount again), N (check number), A (address) and % (split
+            // percentage) carry nothing that a Transaction can hold.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn build(self, account_name: &str) -> BResult<Vec<QifTransaction>> {
+        let date = self.date.ok_or_else(|| {
+            BudgetError::ParseError("QIF transaction is missing a date".to_owned())
+        })?;
+        let cleared = ClearedStatus::parse(&self.cleared)?;
+
+        if self.splits.is_empty() {
+            return Ok(vec![QifTransaction {
+                date,
+                amount: self.amount.ok_or_else(|| {
+                    BudgetError::ParseError("QIF transaction is missing an amount".to_owned())
+                })?,
+                payee: self.payee,
+                memo: self.memo,
+                category: self.category,
+                cleared,
+                account_name: account_name.to_owned(),
+            }]);
+        }
+
+        let payee = self.payee;
+        let memo = self.memo;
+        let category = self.category;
+        self.splits
+            .into_iter()
+            .map(|split| {
+                Ok(QifTransaction {
+                    date,
+                    amount: split.amount.ok_or_else(|| {
+                        BudgetError::ParseError("QIF split is missing an amount".to_owned())
+                    })?,
+                    payee: payee.clone(),
+                    memo: if split.memo.is_empty() {
+                        memo.clone()
+                    } else {
+                        split.memo
+                    },
+                    category: if split.category.is_empty() {
+                        category.clone()
+                    } else {
+                        split.category
+                    },
+                    cleared,
+                    account_name: account_name.to_owned(),
+                })
+            })
+            .collect()
+    }
+}
+
+enum Section {
+    Account,
+    Transactions,
+    Ignored,
+}
+
+fn section_for_header(header: &str) -> BResult<Option<Section>> {
+    let mut parts = header.trim().splitn(2, ':');
+    match (parts.next(), parts.next().map(str::trim)) {
+        (Some("!Option"), _) | (Some("!Clear"), _) => Ok(None),
+        (Some("!Account"), None) => Ok(Some(Section::Account)),
+        (Some("!Type"), Some("Bank"))
+        | (Some("!Type"), Some("CCard"))
+        | (Some("!Type"), Some("Cash")) => Ok(Some(Section::Transactions)),
+        (Some("!Type"), Some("Cat"))
+        | (Some("!Type"), Some("Class"))
+        | (Some("!Type"), Some("Memorized")) => Ok(Some(Section::Ignored)),
+        // Investment and other asset or liability accounts are laid out
+        // differently, so they are left out rather than losing the rest of
+        // the file.
+        (Some("!Type"), Some(other)) => {
+            warn!("Skipping unsupported QIF account type '{}'", other);
+            Ok(Some(Section::Ignored))
+        }
+        _ => Err(BudgetError::ParseError(format!(
+            "Unknown QIF header '{}'",
+            header.trim()
+        ))),
+    }
+}
+
+fn field_name(code: char) -> Option<&'static str> {
+    match code {
+        'D' => Some("date"),
+        'T' | 'U' => Some("amount"),
+        'C' => Some("cleared status"),
+        'S' => Some("split category"),
+        'E' => Some("split memo"),
+        '$' => Some("split amount"),
+        _ => None,
+    }
+}
+
+fn at_line(error: BudgetError, line: u64, field: Option<&str>) -> BudgetError {
+    match error {
+        BudgetError::ParseError(message) => BudgetError::FieldError {
+            line,
+            column: None,
+            field: field.map(str::to_owned),
+            message,
+        },
+        other => other,
+    }
+}
+
+/// Parses a QIF file into transactions. `default_account` is used until an
+/// `!Account` block names the account the following transactions belong to.
+pub fn parse<R: Read>(reader: &mut R, default_account: &str) -> BResult<Vec<QifTransaction>> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    let contents = contents.trim_start_matches('\u{feff}');
+    if !contents.trim_start().starts_with('!') {
+        return Err(BudgetError::ParseError(
+            "QIF files must start with a '!' header".to_owned(),
+        ));
+    }
+
+    let mut transactions = Vec::new();
+    let mut account_name = default_account.to_owned();
+    let mut pending_account = None;
+    let mut section = Section::Ignored;
+    let mut current: Option<PartialTransaction> = None;
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line_number = line_number as u64 + 1;
+        let line = line.trim_end();
+        let mut chars = line.chars();
+        let code = match chars.next() {
+            Some(code) => code,
+            None => continue,
+        };
+        let value = chars.as_str();
+
+        if code == '!' {
+            if let Some(next) =
+                section_for_header(line).map_err(|e| at_line(e, line_number, None))?
+            {
+                section = next;
+            }
+            continue;
+        }
+
+        match section {
+            Section::Account => match code {
+                'N' => pending_account = Some(value.to_owned()),
+                '^' => {
+                    if let Some(name) = pending_account.take() {
+                        account_name = name;
+                    }
+                }
+                _ => {}
+            },
+            Section::Transactions => {
+                if code == '^' {
+                    if let Some(finished) = current.take() {
+                        transactions.append(
+                            &mut finished
+                                .build(&account_name)
+                                .map_err(|e| at_line(e, line_number, None))?,
+                        );
+                    }
+                } else {
+                    current
+                        .get_or_insert_with(Default::default)
+                        .set(code, value)
+                        .map_err(|e| at_line(e, line_number, field_name(code)))?;
+                }
+            }
+            Section::Ignored => {}
+        }
+    }
+
+    if let Some(finished) = current {
+        let last_line = contents.lines().count() as u64;
+        transactions.append(
+            &mut finished
+                .build(&account_name)
+                .map_err(|e| at_line(e, last_line, None))?,
+        );
+    }
+
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> Vec<Transaction> {
+        parse(&mut contents.as_bytes(), "default")
+            .expect("Unable to parse QIF")
+            .into_iter()
+            .map(|t| t.genericize().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parse_single_account() {
+        let transactions = load(
+            "!Type:Bank
+D12/25'19
+T-1,234.56
+CX
+N1001
+PLANDLORD
+MJanuary rent
+LHousing:Rent
+^
+D 1/ 5/2020
+T100.00
+PPAYCHECK
+^
+",
+        );
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].date, Date::ymd(2019, 12, 25));
+        assert_eq!(transactions[0].amount, "1234.56".parse().unwrap());
+        assert_eq!(transactions[0].transaction_type, TransactionType::Debit);
+        assert_eq!(transactions[0].description, "LANDLORD");
+        assert_eq!(transactions[0].category, "Housing:Rent");
+        assert_eq!(transactions[0].notes, "January rent");
+        assert_eq!(transactions[0].labels, "Reconciled");
+        assert_eq!(transactions[0].account_name, "default");
+        assert_eq!(transactions[1].date, Date::ymd(2020, 1, 5));
+        assert_eq!(transactions[1].transaction_type, TransactionType::Credit);
+    }
+
+    #[test]
+    fn parse_splits_and_accounts() {
+        let transactions = load(
+            "!Type:Cat
+NGroceries
+E
+^
+!Option:AutoSwitch
+!Account
+NChecking
+TBank
+^
+!Account
+NVisa
+TCCard
+^
+!Clear:AutoSwitch
+!Type:CCard
+D2019-03-02
+T-120.00
+C*
+PCOSTCO
+SGroceries
+$-100.00
+SHousehold
+EPaper towels
+$-20.00
+^
+",
+        );
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].account_name, "Visa");
+        assert_eq!(transactions[0].category, "Groceries");
+        assert_eq!(transactions[0].amount, "100.00".parse().unwrap());
+        assert_eq!(transactions[0].labels, "Cleared");
+        assert_eq!(transactions[1].category, "Household");
+        assert_eq!(transactions[1].notes, "Paper towels");
+        assert_eq!(transactions[1].description, "COSTCO");
+    }
+
+    #[test]
+    fn skips_unsupported_sections() {
+        let transactions = load(
+            "!Account
+NBrokerage
+TInvst
+^
+!Type:Invst
+D1/2/19
+NBuy
+YACME
+I10.00
+Q5
+T50.00
+^
+!Account
+NChecking
+TBank
+^
+!Type:Bank
+D1/3/69
+T-50.00
+PBROKERAGE
+^
+",
+        );
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].account_name, "Checking");
+        assert_eq!(transactions[0].date, Date::ymd(2069, 1, 3));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(parse(&mut "Date,Amount\n".as_bytes(), "").is_err());
+        assert!(parse(&mut "!Type:Bank\nD13/45/2019\nT1\n^\n".as_bytes(), "").is_err());
+    }
+}
@@ -9,35 +9,182 @@
 use {
     crate::loading::{
         alliant,
+        config::{ConfiguredImporters, ImporterConfig},
+        dedupe::{dedupe, LoadedTransactions},
         generic::{Genericize, Transaction},
-        logix, mint,
+        id::{assign_ids, ensure_unique_ids},
+        logix, mint, ofx, qif,
     },
-    budgetronlib::error::{BResult, BudgetError},
-    csv::Reader,
+    budgetronlib::error::{BResult, BudgetError, ImportAttempt},
+    csv::{self, Reader, StringRecord},
     log::info,
     serde::de::DeserializeOwned,
     std::{
         cmp::min,
-        fmt::Display,
+        fmt,
         fs::File,
         io::{self, Read, Seek, Stdin, StdinLock},
         path::Path,
+        str::FromStr,
     },
 };
 
+/// Turns a csv deserialization error into one that names the line and the
+/// column (by header) of the value that failed.
+fn locate_csv_error(error: csv::Error, headers: &StringRecord) -> BudgetError {
+    let located = match *error.kind() {
+        csv::ErrorKind::Deserialize {
+            pos: Some(ref pos),
+            ref err,
+        } => Some(BudgetError::FieldError {
+            line: pos.line(),
+            column: err.field().map(|field| field + 1),
+            field: err
+                .field()
+                .and_then(|field| headers.get(field as usize))
+                .map(str::to_owned),
+            message: err.kind().to_string(),
+        }),
+        _ => None,
+    };
+    located.unwrap_or_else(|| error.into())
+}
+
 fn from_reader<TransactionType, R>(file: &mut R) -> BResult<Vec<Transaction>>
 where
     TransactionType: Genericize + DeserializeOwned,
     R: io::Read,
 {
+    let mut reader = Reader::from_reader(file);
+    let headers = reader.headers()?.clone();
     let mut transactions = Vec::new();
-    for record in Reader::from_reader(file).deserialize() {
-        let record: TransactionType = record?;
+    for record in reader.deserialize() {
+        let record: TransactionType = record.map_err(|e| locate_csv_error(e, &headers))?;
         transactions.push(record.genericize()?);
     }
     Ok(transactions)
 }
 
+fn from_ofx_reader<R>(file: &mut R) -> BResult<Vec<Transaction>>
+where
+    R: io::Read,
+{
+    ofx::parse(file)?
+        .into_iter()
+        .map(Genericize::genericize)
+        .collect()
+}
+
+fn from_qif_reader<R>(file: &mut R, default_account: &str) -> BResult<Vec<Transaction>>
+where
+    R: io::Read,
+{
+    qif::parse(file, default_account)?
+        .into_iter()
+        .map(Genericize::genericize)
+        .collect()
+}
+
+/// The formats budgetron knows how to read. When a file doesn't say which
+/// format it is in, they are tried in the order given by `Importer::all`.
+enum Importer<'a> {
+    Ofx,
+    Qif,
+    Budgetron,
+    Mint,
+    Logix,
+    Alliant,
+    Configured(&'a ImporterConfig),
+}
+
+impl<'a> Importer<'a> {
+    fn all(configured: &'a [ImporterConfig]) -> Vec<Importer<'a>> {
+        let mut importers = vec![
+            Importer::Ofx,
+            Importer::Qif,
+            Importer::Budgetron,
+            Importer::Mint,
+            Importer::Logix,
+            Importer::Alliant,
+        ];
+        importers.extend(configured.iter().map(Importer::Configured));
+        importers
+    }
+
+    fn find(format: &str, configured: &'a [ImporterConfig]) -> BResult<Importer<'a>> {
+        if format == "qfx" {
+            return Ok(Importer::Ofx);
+        }
+        Importer::all(configured)
+            .into_iter()
+            .find(|importer| importer.name() == format)
+            .ok_or_else(|| BudgetError::UnknownFormat(format.to_owned()))
+    }
+
+    fn name(&self) -> &str {
+        match *self {
+            Importer::Ofx => "ofx",
+            Importer::Qif => "qif",
+            Importer::Budgetron => "budgetron",
+            Importer::Mint => "mint",
+            Importer::Logix => "logix",
+            Importer::Alliant => "alliant",
+            Importer::Configured(config) => config.name(),
+        }
+    }
+
+    fn load<R: io::Read>(
+        &self,
+        reader: &mut R,
+        default_account: &str,
+    ) -> BResult<Vec<Transaction>> {
+        match *self {
+            Importer::Ofx => from_ofx_reader(reader),
+            Importer::Qif => from_qif_reader(reader, default_account),
+            Importer::Budgetron => from_reader::<Transaction, _>(reader),
+            Importer::Mint => from_reader::<mint::MintExport, _>(reader),
+            Importer::Logix => from_reader::<logix::LogixExport, _>(reader),
+            Importer::Alliant => from_reader::<alliant::AlliantExport, _>(reader),
+            Importer::Configured(config) => config.load(reader),
+        }
+    }
+}
+
+/// A file to load, optionally prefixed with the name of the format it is in
+/// (e.g. `mint:transactions.csv`). Without a prefix the format is inferred.
+#[derive(Debug, Clone)]
+pub struct InputFile {
+    format: Option<String>,
+    path: String,
+}
+
+impl FromStr for InputFile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InputFile, String> {
+        match s.find(':') {
+            // A single letter before the colon is a Windows drive, not a format.
+            Some(i) if i > 1 && !s[..i].contains(&['/', '\\', '.'][..]) => Ok(InputFile {
+                format: Some(s[..i].to_owned()),
+                path: s[i + 1..].to_owned(),
+            }),
+            _ => Ok(InputFile {
+                format: None,
+                path: s.to_owned(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for InputFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            Some(ref format) => write!(f, "{} (as {})", self.path, format),
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
 struct StdinSource<'a> {
     buf: Vec<u8>,
     loc: usize,
@@ -120,44 +267,80 @@ impl<'a> Read for Source<'a> {
     }
 }
 
-fn from_file_inferred<P: AsRef<Path> + Copy>(filename: P) -> BResult<Vec<Transaction>> {
+fn from_file_inferred(file: &InputFile, importers: &[ImporterConfig]) -> BResult<Vec<Transaction>> {
+    let path = Path::new(&file.path);
     // If the file doesn't exist. Don't bother.
     let stdin = io::stdin();
-    let mut reader = match filename.as_ref().to_str() {
+    let mut reader = match path.to_str() {
         Some("-") => Source::Stdin(StdinSource::new(&stdin)),
-        _ => Source::File(File::open(filename)?),
+        _ => Source::File(File::open(path)?),
     };
 
-    let mut errors = Vec::new();
+    // QIF files without an `!Account` block don't say which account they
+    // belong to, so fall back to the name of the file.
+    let default_account = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_owned();
 
-    macro_rules! parse_exports {
-        ($($type:path),*) => ($(match from_reader::<$type, _>(&mut reader) {
+    let candidates = match file.format {
+        Some(ref format) => vec![Importer::find(format, importers)?],
+        None => Importer::all(importers),
+    };
+
+    let mut attempts = Vec::new();
+    for importer in candidates {
+        match importer.load(&mut reader, &default_account) {
             Ok(result) => return Ok(result),
-            Err(e) => {
-                errors.push(e);
+            Err(error) => {
+                attempts.push(ImportAttempt {
+                    importer: importer.name().to_owned(),
+                    error,
+                });
                 reader.seek(io::SeekFrom::Start(0))?;
             }
-        })*)
+        }
     }
-    parse_exports!(
-        Transaction,
-        mint::MintExport,
-        logix::LogixExport,
-        alliant::AlliantExport
-    );
-    Err(BudgetError::Multi(errors))
-}
-
-pub fn load_from_files<P: AsRef<Path> + Display, Files: Iterator<Item = P>>(
-    filenames: Files,
-) -> BResult<Vec<Transaction>> {
-    let mut transactions = Vec::new();
-    for filename in filenames {
-        info!("Opening file: {}", filename);
-        transactions.append(&mut from_file_inferred(&filename)?);
+    Err(BudgetError::NoMatchingImporter {
+        file: file.path.clone(),
+        attempts,
+    })
+}
+
+pub fn load_from_files<Files: Iterator<Item = InputFile>>(
+    files: Files,
+    importers: &ConfiguredImporters,
+) -> BResult<LoadedTransactions> {
+    let mut loaded = Vec::new();
+    for file in files {
+        info!("Opening file: {}", file);
+        let mut transactions = from_file_inferred(&file, importers.importers())?;
+        assign_ids(&mut transactions);
+        loaded.push((file.path, transactions));
     }
 
-    transactions.sort_by(|a, b| a.date.cmp(&b.date));
+    let mut loaded = dedupe(loaded, importers.duplicate_window());
+    ensure_unique_ids(&mut loaded.transactions);
+    loaded.transactions.sort_by_key(|t| t.date);
 
-    Ok(transactions)
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_file_format_prefix() {
+        let file: InputFile = "mint:exports/mint.csv".parse().unwrap();
+        assert_eq!(file.format, Some("mint".to_owned()));
+        assert_eq!(file.path, "exports/mint.csv");
+
+        let file: InputFile = "C:\\exports\\mint.csv".parse().unwrap();
+        assert_eq!(file.format, None);
+
+        let file: InputFile = "./odd:name.csv".parse().unwrap();
+        assert_eq!(file.format, None);
+    }
 }
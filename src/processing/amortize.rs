@@ -0,0 +1,127 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{loading::Transaction, processing::config::TransactionMatcher},
+    budgetronlib::fintime::{days_in_month, Date, Timeframe},
+    serde::Deserialize,
+};
+
+/// Spreads the transactions `matcher` matches over `months` monthly portions,
+/// starting on the day of the transaction.
+#[derive(Debug, Deserialize)]
+pub struct AmortizeRule {
+    months: usize,
+    #[serde(flatten)]
+    matcher: TransactionMatcher,
+}
+
+impl AmortizeRule {
+    pub fn matches(&self, t: &Transaction) -> bool {
+        self.months > 1 && self.matcher.matches(t)
+    }
+
+    /// The same day as `date`, `months` months later, or the last day of
+    /// that month if it is shorter.
+    fn months_after(date: Date, months: usize) -> Date {
+        let mut month = date;
+        month.align_to_month();
+        month += Timeframe::Months(months as i64);
+        let last_day = days_in_month(i64::from(month.month()), i64::from(month.year()));
+        month + Timeframe::Days(i64::from(date.day()).min(last_day) - 1)
+    }
+
+    /// The monthly portions of the transaction. Their amounts, and those of
+    /// their splits, add up exactly to the transaction's.
+    pub fn amortize(&self, t: &Transaction, processor: &str, rule: &str) -> Vec<Transaction> {
+        let uid = t.uid();
+        let amounts = t.amount.portions(self.months);
+        let splits = t
+            .splits
+            .iter()
+            .map(|split| split.amount.portions(self.months))
+            .collect::<Vec<_>>();
+        amounts
+            .into_iter()
+            .enumerate()
+            .map(|(i, amount)| {
+                let mut portion = Transaction {
+                    id: format!("{}-amortized-{}", uid, i + 1),
+                    date: AmortizeRule::months_after(t.date, i),
+                    amount,
+                    // The statement balance belongs to the original
+                    // transaction, not to any of its portions.
+                    balance: None,
+                    amortized_from: Some(uid.clone()),
+                    ..t.clone()
+                };
+                for (split, amounts) in portion.splits.iter_mut().zip(&splits) {
+                    split.amount = amounts[i];
+                }
+                let rule = format!("{} ({} of {})", rule, i + 1, self.months);
+                portion.record(processor, rule.clone(), "amount", amount.to_string());
+                portion.record(processor, rule, "date", portion.date.to_string());
+                portion
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::loading::{Money, Split, TransactionType},
+    };
+
+    #[test]
+    fn monthly_portions() {
+        let rule: AmortizeRule = toml::from_str(
+            r#"
+            months = 12
+            description = ["GEICO"]
+            "#,
+        )
+        .expect("Unable to parse rule");
+        let premium = Transaction {
+            id: "geico".to_owned(),
+            date: Date::ymd(2019, 1, 31),
+            original_description: "GEICO AUTO".to_owned(),
+            amount: Money::from_i64(1000),
+            balance: Some(Money::from_i64(-1000)),
+            transaction_type: TransactionType::Debit,
+            splits: vec![Split {
+                amount: Money::from_i64(100),
+                category: "Roadside".to_owned(),
+                person: String::new(),
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+        assert!(rule.matches(&premium));
+
+        let portions = rule.amortize(&premium, "Amortize", "amortize[0]");
+        assert_eq!(portions.len(), 12);
+        assert_eq!(
+            portions.iter().map(|p| p.amount).sum::<Money>(),
+            premium.amount
+        );
+        assert_eq!(
+            portions.iter().map(|p| p.splits[0].amount).sum::<Money>(),
+            Money::from_i64(100)
+        );
+        assert_eq!(portions[1].date, Date::ymd(2019, 2, 28));
+        assert_eq!(portions[11].date, Date::ymd(2019, 12, 31));
+        assert_eq!(portions[11].id, "geico-amortized-12");
+        assert!(portions
+            .iter()
+            .all(|p| p.amortized_from == Some("geico".to_owned())));
+        assert!(portions.iter().all(|p| p.balance.is_none()));
+        assert_eq!(portions[11].provenance[0].rule, "amortize[0] (12 of 12)");
+    }
+}
@@ -8,12 +8,22 @@
 
 use {
     crate::{
-        loading::{Money, Transaction},
-        processing::{regex::Regex, Collate, RefundCollator, TransferCollator},
+        loading::{Currency, Money, OwnerShare, Split, Transaction, TransactionType},
+        processing::{
+            check_single_currency, regex::Regex, shares, split, AmortizeRule, CategoryClassifier,
+            Collate, CurrencyConverter, DroppedTransaction, DuplicateCollator, DuplicateOptions,
+            ExchangeRates, PartialRefundCollator, RefundCollator, RewriteRule, ShareRule,
+            SplitRule, SuggestOptions, TransferCollator, TransferOptions, Window,
+            SUGGEST_CATEGORIES,
+        },
     },
-    budgetronlib::{error::BResult, fintime::Date},
+    budgetronlib::{
+        error::{BResult, BudgetError},
+        fintime::Date,
+    },
+    log::warn,
     serde::Deserialize,
-    std::collections::HashMap,
+    std::{cmp::Reverse, collections::HashMap},
 };
 
 #[derive(Debug, Deserialize)]
@@ -24,6 +34,13 @@ pub struct ConfiguredProcessors {
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum Processor {
+    // Listed before `Categorize`, which would otherwise accept its `categories`
+    // and ignore the rules.
+    CategorizeByRule {
+        category_rules: Vec<CategoryRule>,
+        #[serde(default)]
+        categories: HashMap<String, Vec<String>>,
+    },
     Categorize {
         categories: HashMap<String, Vec<String>>,
     },
@@ -47,26 +64,191 @@ pub enum Processor {
     },
     Transfers {
         transfer_horizon: usize,
+        #[serde(flatten)]
+        options: TransferOptions,
+    },
+    TransfersWithin {
+        transfer_window: i64,
+        #[serde(flatten)]
+        options: TransferOptions,
     },
     Refunds {
         refund_horizon: usize,
     },
+    RefundsWithin {
+        refund_window: i64,
+    },
+    PartialRefunds {
+        partial_refund_window: i64,
+        #[serde(default = "default_merchant_similarity")]
+        merchant_similarity: f64,
+    },
+    Rewrite {
+        rewrite: Vec<RewriteRule>,
+    },
+    Split {
+        split: Vec<SplitRule>,
+    },
+    SplitNotes {
+        split_notes: String,
+    },
+    Amortize {
+        amortize: Vec<AmortizeRule>,
+    },
+    SuggestCategories {
+        #[serde(flatten)]
+        options: SuggestOptions,
+    },
+    DuplicateCharges {
+        duplicate_window: i64,
+        #[serde(flatten)]
+        options: DuplicateOptions,
+    },
+    OwnerShares {
+        owner_shares: Vec<ShareRule>,
+    },
+    ShareNotes {
+        share_notes: String,
+    },
+    Currencies {
+        reporting_currency: Currency,
+        #[serde(default)]
+        account_currency: HashMap<String, Currency>,
+        exchange_rates: Option<String>,
+    },
+}
+
+fn default_merchant_similarity() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AmountSign {
+    /// Money coming into the account, i.e. credits.
+    Positive,
+    /// Money leaving the account, i.e. debits and transfers.
+    Negative,
+}
+
+/// A `TransactionType` as matchers spell it. The capitalized names that
+/// transactions are exported with are still accepted.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedType {
+    #[serde(alias = "Debit")]
+    Debit,
+    #[serde(alias = "Credit")]
+    Credit,
+    #[serde(alias = "Transfer")]
+    Transfer,
 }
 
+impl From<MatchedType> for TransactionType {
+    fn from(matched: MatchedType) -> TransactionType {
+        match matched {
+            MatchedType::Debit => TransactionType::Debit,
+            MatchedType::Credit => TransactionType::Credit,
+            MatchedType::Transfer => TransactionType::Transfer,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Decides which transactions a processor applies to.
+///
+/// A matcher matches when any one of its field predicates (`account`,
+/// `description`, `category`, `note`, `label`, `person`, `tag`,
+/// `transaction_type`, `sign`, `weekday`, `range`) matches. `only_before`,
+/// `only_after` and the `all`, `any` and `not` groups must additionally
+/// hold, so conditions can be combined:
+///
+/// ```toml
+/// all = [
+///     { description = ["AMAZON"] },
+///     { account = ["Joint Card"] },
+///     { range = { low = 50.0 } },
+/// ]
+/// ```
 #[derive(Debug, Deserialize)]
 pub struct TransactionMatcher {
     account: Option<Vec<Regex>>,
     description: Option<Vec<Regex>>,
     category: Option<Vec<Regex>>,
     note: Option<Vec<Regex>>,
+    label: Option<Vec<Regex>>,
+    person: Option<Vec<Regex>>,
+    tag: Option<Vec<Regex>>,
+    transaction_type: Option<Vec<MatchedType>>,
+    sign: Option<AmountSign>,
+    weekday: Option<Vec<Weekday>>,
     range: Option<MoneyRange>,
 
     only_before: Option<Date>,
     only_after: Option<Date>,
+
+    all: Option<Vec<TransactionMatcher>>,
+    any: Option<Vec<TransactionMatcher>>,
+    not: Option<Box<TransactionMatcher>>,
+}
+
+fn any_match(regexes: &Option<Vec<Regex>>, text: &str) -> Option<bool> {
+    regexes.as_ref().map(|r| r.iter().any(|v| v.is_match(text)))
 }
 
 impl TransactionMatcher {
-    fn matches(&self, t: &Transaction) -> bool {
+    fn has_group(&self) -> bool {
+        self.all.is_some() || self.any.is_some() || self.not.is_some()
+    }
+
+    /// Whether any of the field predicates matches, or `None` if there are
+    /// no field predicates.
+    fn fields_match(&self, t: &Transaction) -> Option<bool> {
+        let predicates = [
+            any_match(&self.description, &t.original_description),
+            any_match(&self.category, &t.original_category),
+            any_match(&self.account, &t.account_name),
+            any_match(&self.note, &t.notes),
+            any_match(&self.label, &t.labels),
+            any_match(&self.person, &t.person),
+            self.tag.as_ref().map(|tags| {
+                tags.iter()
+                    .any(|tag| t.tags.iter().any(|existing| tag.is_match(existing)))
+            }),
+            self.transaction_type.as_ref().map(|types| {
+                types
+                    .iter()
+                    .any(|&ty| TransactionType::from(ty) == t.transaction_type)
+            }),
+            self.sign.map(|sign| {
+                let positive = t.transaction_type.is_credit();
+                (sign == AmountSign::Positive) == positive
+            }),
+            self.weekday
+                .as_ref()
+                .map(|days| days.iter().any(|&day| day as u32 == t.date.weekday())),
+            self.range.as_ref().map(|range| range.contains(t.amount)),
+        ];
+        predicates
+            .iter()
+            .fold(None, |matched, predicate| match (matched, *predicate) {
+                (Some(a), Some(b)) => Some(a || b),
+                (a, b) => a.or(b),
+            })
+    }
+
+    pub fn matches(&self, t: &Transaction) -> bool {
         if let Some(only_before) = self.only_before {
             if t.date > only_before {
                 return false;
@@ -79,82 +261,296 @@ impl TransactionMatcher {
             }
         }
 
-        if let Some(ref description) = self.description {
-            if description
-                .iter()
-                .any(|v| v.is_match(&t.original_description))
-            {
-                return true;
-            }
-        }
-        if let Some(ref category) = self.category {
-            if category.iter().any(|v| v.is_match(&t.original_category)) {
-                return true;
+        if let Some(ref all) = self.all {
+            if !all.iter().all(|m| m.matches(t)) {
+                return false;
             }
         }
-        if let Some(ref account) = self.account {
-            if account.iter().any(|v| v.is_match(&t.account_name)) {
-                return true;
+        if let Some(ref any) = self.any {
+            if !any.iter().any(|m| m.matches(t)) {
+                return false;
             }
         }
-        if let Some(ref note) = self.note {
-            if note.iter().any(|v| v.is_match(&t.notes)) {
-                return true;
+        if let Some(ref not) = self.not {
+            if not.matches(t) {
+                return false;
             }
         }
-        if let Some(ref range) = self.range {
-            if t.amount >= range.low && t.amount < range.high {
-                return true;
-            }
+
+        match self.fields_match(t) {
+            Some(matched) => matched,
+            None => self.has_group(),
         }
-        false
     }
 }
 
+/// Amounts from `low` (inclusive) up to `high` (exclusive). Either end may be
+/// left out.
 #[derive(Debug, Deserialize)]
 pub struct MoneyRange {
-    low: Money,
-    high: Money,
+    low: Option<Money>,
+    high: Option<Money>,
+}
+
+impl MoneyRange {
+    fn contains(&self, amount: Money) -> bool {
+        self.low.iter().all(|&low| amount >= low) && self.high.iter().all(|&high| amount < high)
+    }
+}
+
+/// Assigns `category` to every transaction `matcher` matches. When several
+/// rules match, the one with the highest `priority` wins, and among those the
+/// one listed first.
+#[derive(Debug, Deserialize)]
+pub struct CategoryRule {
+    category: String,
+    #[serde(default)]
+    priority: i64,
+    #[serde(flatten)]
+    matcher: TransactionMatcher,
+}
+
+/// Notes that a split or share could not be worked out, and leaves the
+/// transaction as it was, rather than stop processing over one transaction.
+fn record_ignored(
+    processor: &str,
+    rule: String,
+    transaction: &mut Transaction,
+    field: &str,
+    error: BudgetError,
+) {
+    warn!("{}", error);
+    transaction.record(processor, rule, field, format!("ignored: {}", error));
+}
+
+fn record_splits(
+    processor: &str,
+    rule: String,
+    transaction: &mut Transaction,
+    splits: BResult<Vec<Split>>,
+) {
+    let splits = match splits {
+        Ok(splits) => splits,
+        Err(e) => return record_ignored(processor, rule, transaction, "splits", e),
+    };
+    let value = splits
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    transaction.record(processor, rule, "splits", value);
+    transaction.splits = splits;
+}
+
+fn record_shares(
+    processor: &str,
+    rule: String,
+    transaction: &mut Transaction,
+    shares: BResult<Vec<OwnerShare>>,
+) {
+    let shares = match shares {
+        Ok(shares) => shares,
+        Err(e) => return record_ignored(processor, rule, transaction, "owner_shares", e),
+    };
+    let value = shares
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ");
+    transaction.record(processor, rule, "owner_shares", value);
+    transaction.owner_shares = shares;
+}
+
+/// Renames a transaction's original category using a map from new category
+/// names to the original categories they replace.
+fn categorize(
+    processor: &str,
+    categories: &HashMap<String, Vec<String>>,
+    transaction: &mut Transaction,
+) {
+    for (key, values) in categories {
+        let cat = &transaction.original_category;
+        if key == cat || (!values.is_empty() && values.contains(&cat.to_owned())) {
+            transaction.category = key.clone();
+            transaction.record(
+                processor,
+                format!("categories.{}", key),
+                "category",
+                key.clone(),
+            );
+        }
+    }
+}
+
+/// Removes the transactions `hide` gives a reason for.
+fn hide<F>(
+    processor: &str,
+    transactions: Vec<Transaction>,
+    dropped: &mut Vec<DroppedTransaction>,
+    mut hide: F,
+) -> Vec<Transaction>
+where
+    F: FnMut(&Transaction) -> Option<String>,
+{
+    let mut kept = Vec::with_capacity(transactions.len());
+    for transaction in transactions {
+        match hide(&transaction) {
+            Some(reason) => dropped.push(DroppedTransaction::new(transaction, processor, reason)),
+            None => kept.push(transaction),
+        }
+    }
+    kept
+}
+
+impl ConfiguredProcessors {
+    /// The options of the first processor that suggests categories, if any.
+    pub fn suggest_options(&self) -> Option<&SuggestOptions> {
+        self.processor.iter().find_map(|p| match *p {
+            Processor::SuggestCategories { ref options } => Some(options),
+            _ => None,
+        })
+    }
 }
 
 impl Collate for ConfiguredProcessors {
-    fn collate(&self, mut transactions: Vec<Transaction>) -> BResult<Vec<Transaction>> {
+    fn collate(
+        &self,
+        mut transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
+        // Amounts do not carry their currency, so they have to be converted
+        // before anything adds them up or compares them.
+        let currencies = self.processor.iter().position(|p| p.name() == "Currencies");
+        if let Some(currencies) = currencies {
+            if let Some(p) = self.processor[..currencies]
+                .iter()
+                .find(|p| p.compares_amounts())
+            {
+                return Err(BudgetError::ConfigError(format!(
+                    "The {} processor compares amounts, so it has to come after the processor \
+                     with a reporting_currency",
+                    p.name()
+                )));
+            }
+        }
         for p in &self.processor {
-            transactions = p.collate(transactions)?;
+            transactions = p.collate(transactions, dropped)?;
         }
+        check_single_currency(&transactions)?;
         Ok(transactions)
     }
 }
 
+impl Processor {
+    /// The name processor changes are recorded under.
+    pub fn name(&self) -> &'static str {
+        use self::Processor::*;
+        match *self {
+            CategorizeByRule { .. } => "CategorizeByRule",
+            Categorize { .. } => "Categorize",
+            AssignOwners { .. } => "AssignOwners",
+            OverrideOwners { .. } => "OverrideOwners",
+            AddTags { .. } => "AddTags",
+            OwnersForTag { .. } => "OwnersForTag",
+            HideAccount { .. } => "HideAccount",
+            HideDescription { .. } => "HideDescription",
+            Transfers { .. } | TransfersWithin { .. } => "Transfers",
+            Refunds { .. } | RefundsWithin { .. } => "Refunds",
+            PartialRefunds { .. } => "PartialRefunds",
+            Rewrite { .. } => "Rewrite",
+            Split { .. } => "Split",
+            SplitNotes { .. } => "SplitNotes",
+            Amortize { .. } => "Amortize",
+            SuggestCategories { .. } => SUGGEST_CATEGORIES,
+            DuplicateCharges { .. } => "DuplicateCharges",
+            OwnerShares { .. } => "OwnerShares",
+            ShareNotes { .. } => "ShareNotes",
+            Currencies { .. } => "Currencies",
+        }
+    }
+}
+
+impl Processor {
+    /// Whether the processor adds up or compares the amounts of transactions.
+    fn compares_amounts(&self) -> bool {
+        [
+            "Transfers",
+            "Refunds",
+            "PartialRefunds",
+            "DuplicateCharges",
+            "Split",
+            "SplitNotes",
+            "Amortize",
+            "OwnerShares",
+            "ShareNotes",
+        ]
+        .contains(&self.name())
+    }
+}
+
 impl Collate for Processor {
-    fn collate(&self, mut transactions: Vec<Transaction>) -> BResult<Vec<Transaction>> {
+    fn collate(
+        &self,
+        mut transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
         use self::Processor::*;
+        let name = self.name();
         match *self {
-            Categorize { ref categories } => {
+            CategorizeByRule {
+                ref category_rules,
+                ref categories,
+            } => {
+                let mut rules = category_rules.iter().enumerate().collect::<Vec<_>>();
+                rules.sort_by_key(|(_, rule)| Reverse(rule.priority));
                 for transaction in &mut transactions {
-                    let cat = &transaction.original_category;
-                    for (key, values) in categories {
-                        if key == cat || (!values.is_empty() && values.contains(&cat.to_owned())) {
-                            transaction.category = key.clone();
+                    match rules
+                        .iter()
+                        .find(|(_, rule)| rule.matcher.matches(transaction))
+                    {
+                        Some((i, rule)) => {
+                            transaction.category = rule.category.clone();
+                            transaction.record(
+                                name,
+                                format!("category_rules[{}]", i),
+                                "category",
+                                rule.category.clone(),
+                            );
                         }
+                        None => categorize(name, categories, transaction),
                     }
                 }
             }
+            Categorize { ref categories } => {
+                for transaction in &mut transactions {
+                    categorize(name, categories, transaction);
+                }
+            }
             AssignOwners { ref owners } => {
                 for transaction in &mut transactions {
                     for (owner, matcher) in owners {
                         if matcher.matches(transaction) {
                             transaction.person = owner.clone();
+                            transaction.record(
+                                name,
+                                format!("owners.{}", owner),
+                                "person",
+                                owner.clone(),
+                            );
                         }
                     }
                 }
             }
             OverrideOwners { ref owner_override } => {
+                let rule = format!("owner_override = {:?}", owner_override.as_str());
                 for transaction in &mut transactions {
-                    if let Some(captures) = owner_override.captures(&transaction.notes) {
-                        if let Some(new_owner) = captures.get(1) {
-                            transaction.person = new_owner.as_str().to_owned();
-                        }
+                    let new_owner = owner_override
+                        .captures(&transaction.notes)
+                        .and_then(|captures| captures.get(1))
+                        .map(|new_owner| new_owner.as_str().to_owned());
+                    if let Some(new_owner) = new_owner {
+                        transaction.person = new_owner.clone();
+                        transaction.record(name, rule.clone(), "person", new_owner);
                     }
                 }
             }
@@ -163,6 +559,7 @@ impl Collate for Processor {
                     for (tag, matcher) in tags {
                         if matcher.matches(transaction) {
                             transaction.tags.push(tag.to_owned());
+                            transaction.record(name, format!("tags.{}", tag), "tags", tag.clone());
                         }
                     }
                 }
@@ -172,30 +569,462 @@ impl Collate for Processor {
                     for (tag, owner) in tag_owner {
                         if transaction.tags.contains(tag) {
                             transaction.person = owner.to_owned();
+                            transaction.record(
+                                name,
+                                format!("tag_owner.{}", tag),
+                                "person",
+                                owner.clone(),
+                            );
                         }
                     }
                 }
             }
             HideAccount { ref hide_accounts } => {
-                transactions.retain(|t| !hide_accounts.contains(&t.account_name))
+                transactions = hide(name, transactions, dropped, |t| {
+                    if hide_accounts.contains(&t.account_name) {
+                        Some(format!("account {:?} is hidden", t.account_name))
+                    } else {
+                        None
+                    }
+                })
             }
             HideDescription {
                 ref hide_description,
-            } => transactions.retain(|t| {
-                for d in hide_description {
-                    if d.is_match(&t.description) {
-                        return false;
+            } => {
+                transactions = hide(name, transactions, dropped, |t| {
+                    hide_description
+                        .iter()
+                        .find(|d| d.is_match(&t.description))
+                        .map(|d| format!("description matches {:?}", d.as_str()))
+                })
+            }
+            Transfers {
+                transfer_horizon,
+                ref options,
+            } => {
+                transactions = TransferCollator::with_options(
+                    Window::Transactions(transfer_horizon),
+                    options.clone(),
+                )
+                .collate(transactions, dropped)?;
+            }
+            TransfersWithin {
+                transfer_window,
+                ref options,
+            } => {
+                transactions =
+                    TransferCollator::with_options(Window::Days(transfer_window), options.clone())
+                        .collate(transactions, dropped)?;
+            }
+            Refunds { refund_horizon } => {
+                transactions =
+                    RefundCollator::new(refund_horizon).collate(transactions, dropped)?;
+            }
+            RefundsWithin { refund_window } => {
+                transactions = RefundCollator::within(Window::Days(refund_window))
+                    .collate(transactions, dropped)?;
+            }
+            PartialRefunds {
+                partial_refund_window,
+                merchant_similarity,
+            } => {
+                transactions =
+                    PartialRefundCollator::new(partial_refund_window, merchant_similarity)
+                        .collate(transactions, dropped)?;
+            }
+            DuplicateCharges {
+                duplicate_window,
+                ref options,
+            } => {
+                transactions = DuplicateCollator::new(Window::Days(duplicate_window), options)
+                    .collate(transactions, dropped)?;
+            }
+            Rewrite { ref rewrite } => {
+                for transaction in &mut transactions {
+                    for rule in rewrite {
+                        rule.apply(transaction);
                     }
                 }
-                true
-            }),
-            Transfers { transfer_horizon } => {
-                transactions = TransferCollator::new(transfer_horizon).collate(transactions)?;
             }
-            Refunds { refund_horizon } => {
-                transactions = RefundCollator::new(refund_horizon).collate(transactions)?;
+            Split { split: ref rules } => {
+                for transaction in &mut transactions {
+                    if !transaction.splits.is_empty() {
+                        continue;
+                    }
+                    if let Some((i, rule)) = rules
+                        .iter()
+                        .enumerate()
+                        .find(|(_, r)| r.matches(transaction))
+                    {
+                        let splits = split::split(rule.parts(), transaction);
+                        record_splits(name, format!("split[{}]", i), transaction, splits);
+                    }
+                }
+            }
+            SplitNotes { ref split_notes } => {
+                for transaction in &mut transactions {
+                    if let Some(parts) = split::parse_notes(&transaction.notes, split_notes) {
+                        let splits = split::split(&parts, transaction);
+                        record_splits(
+                            name,
+                            format!("split_notes = {:?}", split_notes),
+                            transaction,
+                            splits,
+                        );
+                    }
+                }
+            }
+            OwnerShares {
+                owner_shares: ref rules,
+            } => {
+                for transaction in &mut transactions {
+                    if !transaction.owner_shares.is_empty() {
+                        continue;
+                    }
+                    if let Some((i, rule)) = rules
+                        .iter()
+                        .enumerate()
+                        .find(|(_, r)| r.matches(transaction))
+                    {
+                        let shares = shares::owner_shares(&rule.shares(), transaction);
+                        record_shares(name, format!("owner_shares[{}]", i), transaction, shares);
+                    }
+                }
+            }
+            ShareNotes { ref share_notes } => {
+                for transaction in &mut transactions {
+                    if let Some(shares) = shares::parse_notes(&transaction.notes, share_notes) {
+                        let shares = shares::owner_shares(&shares, transaction);
+                        record_shares(
+                            name,
+                            format!("share_notes = {:?}", share_notes),
+                            transaction,
+                            shares,
+                        );
+                    }
+                }
+            }
+            SuggestCategories { ref options } => {
+                let classifier = CategoryClassifier::train(&transactions, options);
+                for transaction in transactions
+                    .iter_mut()
+                    .filter(|t| options.is_uncategorized(t))
+                {
+                    if let Some(suggestion) = classifier.suggest(transaction) {
+                        if suggestion.confidence >= options.threshold {
+                            transaction.record(
+                                name,
+                                format!("confidence = {:.2}", suggestion.confidence),
+                                "category",
+                                suggestion.category.clone(),
+                            );
+                            transaction.category = suggestion.category;
+                        }
+                    }
+                }
+            }
+            Amortize { ref amortize } => {
+                let mut portions = Vec::new();
+                transactions = hide(name, transactions, dropped, |t| {
+                    let (i, rule) = amortize.iter().enumerate().find(|(_, r)| r.matches(t))?;
+                    let amortized = rule.amortize(t, name, &format!("amortize[{}]", i));
+                    let reason = format!(
+                        "amortized from {} to {}",
+                        amortized[0].date,
+                        amortized[amortized.len() - 1].date
+                    );
+                    portions.extend(amortized);
+                    Some(reason)
+                });
+                if !portions.is_empty() {
+                    transactions.extend(portions);
+                    transactions.sort_by_key(|t| t.date);
+                }
+            }
+            Currencies {
+                reporting_currency,
+                ref account_currency,
+                ref exchange_rates,
+            } => {
+                let rates = match *exchange_rates {
+                    Some(ref path) => ExchangeRates::load(path)?,
+                    None => ExchangeRates::default(),
+                };
+                transactions = CurrencyConverter::new(reporting_currency, account_currency, rates)
+                    .collate(transactions, dropped)?;
             }
         }
         Ok(transactions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::processing::{collate_all, Collator},
+    };
+
+    fn matcher(config: &str) -> TransactionMatcher {
+        toml::from_str(config).expect("Unable to parse matcher")
+    }
+
+    fn purchase(description: &str, account: &str, amount: i64) -> Transaction {
+        Transaction {
+            // A Saturday.
+            date: Date::ymd(2019, 6, 1),
+            description: description.to_owned(),
+            original_description: description.to_owned(),
+            account_name: account.to_owned(),
+            amount: Money::from_i64(amount),
+            transaction_type: TransactionType::Debit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fields_match_any() {
+        let m = matcher(
+            r#"
+            description = ["AMAZON"]
+            account = ["Joint Card"]
+            "#,
+        );
+        assert!(m.matches(&purchase("AMAZON", "Visa", 10)));
+        assert!(m.matches(&purchase("GROCER", "Joint Card", 10)));
+        assert!(!m.matches(&purchase("GROCER", "Visa", 10)));
+    }
+
+    #[test]
+    fn groups() {
+        let m = matcher(
+            r#"
+            all = [
+                { description = ["AMAZON"] },
+                { account = ["Joint Card"] },
+                { range = { low = 50.0 } },
+            ]
+            not = { weekday = ["sunday"] }
+            "#,
+        );
+        assert!(m.matches(&purchase("AMAZON", "Joint Card", 60)));
+        assert!(!m.matches(&purchase("AMAZON", "Joint Card", 40)));
+        assert!(!m.matches(&purchase("AMAZON", "Visa", 60)));
+
+        let mut sunday = purchase("AMAZON", "Joint Card", 60);
+        sunday.date = Date::ymd(2019, 6, 2);
+        assert!(!m.matches(&sunday));
+
+        let m = matcher(r#"any = [{ person = ["^Zach$"] }, { tag = ["^recurring$"] }]"#);
+        let mut t = purchase("HULU", "Visa", 10);
+        assert!(!m.matches(&t));
+        t.tags.push("recurring".to_owned());
+        assert!(m.matches(&t));
+    }
+
+    #[test]
+    fn new_predicates() {
+        let t = purchase("COFFEE", "Visa", 5);
+        assert!(matcher(r#"transaction_type = ["debit"]"#).matches(&t));
+        assert!(matcher(r#"transaction_type = ["Debit"]"#).matches(&t));
+        assert!(!matcher(r#"transaction_type = ["credit", "transfer"]"#).matches(&t));
+        assert!(matcher(r#"sign = "negative""#).matches(&t));
+        assert!(!matcher(r#"sign = "positive""#).matches(&t));
+        assert!(matcher(r#"weekday = ["saturday", "sunday"]"#).matches(&t));
+        assert!(!matcher(r#"label = ["."]"#).matches(&t));
+        assert!(!matcher("").matches(&t));
+    }
+
+    #[test]
+    fn categorize_by_rule() {
+        let processors: ConfiguredProcessors = toml::from_str(
+            r#"
+            [[processor]]
+            [processor.categories]
+            Shopping = ["Merchandise"]
+
+            [[processor.category_rules]]
+            category = "Groceries"
+            description = ["SAFEWAY"]
+
+            [[processor.category_rules]]
+            category = "Household"
+            priority = 10
+            all = [{ description = ["SAFEWAY"] }, { range = { low = 100.0 } }]
+            "#,
+        )
+        .expect("Unable to parse processors");
+
+        let mut merchandise = purchase("AMAZON", "Visa", 30);
+        merchandise.original_category = "Merchandise".to_owned();
+        let transactions = processors
+            .collate(
+                vec![
+                    purchase("SAFEWAY", "Visa", 20),
+                    purchase("SAFEWAY", "Visa", 150),
+                    merchandise,
+                    purchase("PAYROLL", "Checking", 1000),
+                ],
+                &mut Vec::new(),
+            )
+            .expect("Unable to categorize");
+        let categories = transactions
+            .iter()
+            .map(|t| t.category.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(categories, vec!["Groceries", "Household", "Shopping", ""]);
+    }
+
+    #[test]
+    fn provenance_and_dropped() {
+        let processors: ConfiguredProcessors = toml::from_str(
+            r#"
+            [[processor]]
+            hide_accounts = ["Old Card"]
+
+            [[processor]]
+            refund_horizon = 10
+
+            [[processor]]
+            [processor.owners.Zach]
+            account = ["Visa"]
+
+            [[processor]]
+            [processor.tags.coffee]
+            description = ["COFFEE"]
+            "#,
+        )
+        .expect("Unable to parse processors");
+
+        let mut refund = purchase("SHOES", "Visa", 60);
+        refund.transaction_type = TransactionType::Credit;
+        let mut dropped = Vec::new();
+        let transactions = processors
+            .collate(
+                vec![
+                    purchase("COFFEE", "Visa", 5),
+                    purchase("COFFEE", "Old Card", 5),
+                    purchase("SHOES", "Visa", 60),
+                    refund,
+                ],
+                &mut dropped,
+            )
+            .expect("Unable to process");
+
+        assert_eq!(transactions.len(), 1);
+        let changes = transactions[0]
+            .provenance
+            .iter()
+            .map(|c| (c.processor.as_str(), c.rule.as_str(), c.field.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("AssignOwners", "owners.Zach", "person"),
+                ("AddTags", "tags.coffee", "tags"),
+            ]
+        );
+
+        let reasons = dropped
+            .iter()
+            .map(|d| (d.processor.as_str(), d.transaction.description.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                ("HideAccount", "COFFEE"),
+                ("Refunds", "SHOES"),
+                ("Refunds", "SHOES"),
+            ]
+        );
+        assert_eq!(dropped[0].reason, r#"account "Old Card" is hidden"#);
+    }
+
+    #[test]
+    fn synthetic_ids_are_unique() {
+        let processors: ConfiguredProcessors = toml::from_str(
+            r#"
+            [[processor]]
+            [[processor.amortize]]
+            months = 2
+            description = ["GEICO"]
+            "#,
+        )
+        .expect("Unable to parse processors");
+        let mut premium = purchase("GEICO", "Visa", 100);
+        premium.id = "geico".to_owned();
+        let mut taken = purchase("COFFEE", "Visa", 5);
+        taken.id = "geico-amortized-2".to_owned();
+
+        let collated = collate_all(vec![taken, premium], &[Collator::Config(processors)]).unwrap();
+        let mut ids = collated
+            .transactions
+            .iter()
+            .map(Transaction::uid)
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.contains(&"geico-amortized-2".to_owned()));
+    }
+
+    #[test]
+    fn currencies_come_first() {
+        let processors: ConfiguredProcessors = toml::from_str(
+            r#"
+            [[processor]]
+            refund_horizon = 10
+
+            [[processor]]
+            reporting_currency = "USD"
+            "#,
+        )
+        .expect("Unable to parse processors");
+        let mut purchase = purchase("SHOES", "Visa", 60);
+        purchase.currency = Some("EUR".parse().unwrap());
+        let mut refund = purchase.clone();
+        refund.transaction_type = TransactionType::Credit;
+        refund.currency = Some("USD".parse().unwrap());
+
+        let mut dropped = Vec::new();
+        assert!(processors
+            .collate(vec![purchase.clone(), refund.clone()], &mut dropped)
+            .is_err());
+
+        // Without a conversion, the pair is still not netted, but it can not
+        // be reported either.
+        let processors: ConfiguredProcessors =
+            toml::from_str("[[processor]]\nrefund_horizon = 10").unwrap();
+        assert!(processors
+            .collate(vec![purchase, refund], &mut dropped)
+            .is_err());
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn bad_notes_are_ignored() {
+        let processors: ConfiguredProcessors = toml::from_str(
+            r#"
+            [[processor]]
+            split_notes = "split:"
+
+            [[processor]]
+            share_notes = "shares:"
+            "#,
+        )
+        .expect("Unable to parse processors");
+        let mut bad = purchase("COSTCO", "Visa", 100);
+        bad.notes = "split: 150 Groceries\nshares: Zach 0%".to_owned();
+        let mut good = purchase("COSTCO", "Visa", 100);
+        good.notes = "split: 40 Groceries".to_owned();
+
+        let transactions = processors
+            .collate(vec![bad, good], &mut Vec::new())
+            .expect("One bad note should not stop processing");
+        assert!(transactions[0].splits.is_empty());
+        assert!(transactions[0].owner_shares.is_empty());
+        assert!(transactions[0].provenance[0].value.starts_with("ignored: "));
+        assert_eq!(transactions[0].provenance[1].field, "owner_shares");
+        assert_eq!(transactions[1].splits.len(), 2);
+    }
+}
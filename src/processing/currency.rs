@@ -0,0 +1,281 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Currency, Transaction},
+        processing::{Collate, DroppedTransaction},
+    },
+    budgetronlib::{
+        error::{BResult, BudgetError},
+        fintime::Date,
+    },
+    serde::Deserialize,
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        fs::File,
+        io::Read,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+struct ExchangeRateRecord {
+    date: String,
+    pair: String,
+    rate: f64,
+}
+
+/// Historical exchange rates, loaded from a CSV file with `date`, `pair` and
+/// `rate` columns. A row with a pair of `EUR/USD` and a rate of `1.1` means
+/// that one euro bought 1.1 dollars on that date. Dates may be written as
+/// `2019-01-31` or `1/31/2019`.
+#[derive(Debug, Default)]
+pub struct ExchangeRates {
+    rates: HashMap<(Currency, Currency), BTreeMap<Date, f64>>,
+}
+
+impl ExchangeRates {
+    pub fn load(path: &str) -> BResult<ExchangeRates> {
+        ExchangeRates::from_reader(&mut File::open(path)?)
+    }
+
+    pub fn from_reader<R: Read>(reader: &mut R) -> BResult<ExchangeRates> {
+        let mut rates = ExchangeRates::default();
+        let mut csv = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        for record in csv.deserialize() {
+            let record: ExchangeRateRecord = record?;
+            let date = Date::parse_with_format(&record.date, "%Y-%m-%d")
+                .or_else(|_| record.date.parse())
+                .map_err(BudgetError::ParseError)?;
+            let pair = parse_pair(&record.pair)?;
+            if !record.rate.is_finite() || record.rate <= 0.0 {
+                return Err(BudgetError::ParseError(format!(
+                    "Exchange rate for {} on {} must be positive",
+                    record.pair, date
+                )));
+            }
+            rates
+                .rates
+                .entry(pair)
+                .or_insert_with(BTreeMap::new)
+                .insert(date, record.rate);
+        }
+        Ok(rates)
+    }
+
+    /// The most recent rate for converting `from` into `to` that was known on
+    /// `date`. Rates are only ever listed in one direction, so the inverse
+    /// pair is consulted as well.
+    pub fn rate(&self, from: Currency, to: Currency, date: Date) -> Option<f64> {
+        if from == to {
+            return Some(1.0);
+        }
+        let latest = |pair| {
+            self.rates
+                .get(&pair)
+                .and_then(|rates| rates.range(..=date).next_back())
+                .map(|(_, &rate)| rate)
+        };
+        latest((from, to)).or_else(|| latest((to, from)).map(|rate| 1.0 / rate))
+    }
+}
+
+fn parse_pair(pair: &str) -> BResult<(Currency, Currency)> {
+    let mut parts = pair.split('/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(from), Some(to), None) => Ok((
+            from.parse().map_err(BudgetError::ParseError)?,
+            to.parse().map_err(BudgetError::ParseError)?,
+        )),
+        _ => Err(BudgetError::ParseError(format!(
+            "Currency pair '{}' should look like EUR/USD",
+            pair
+        ))),
+    }
+}
+
+/// Converts every transaction into a single reporting currency. Transactions
+/// that were not imported with a currency use the currency of their account,
+/// and are assumed to already be in the reporting currency otherwise.
+///
+/// `Money` does not know its currency, so this has to run before any
+/// processor that adds up or compares amounts, which `ConfiguredProcessors`
+/// makes sure of.
+pub struct CurrencyConverter<'a> {
+    reporting_currency: Currency,
+    account_currency: &'a HashMap<String, Currency>,
+    rates: ExchangeRates,
+}
+
+impl<'a> CurrencyConverter<'a> {
+    pub fn new(
+        reporting_currency: Currency,
+        account_currency: &'a HashMap<String, Currency>,
+        rates: ExchangeRates,
+    ) -> CurrencyConverter<'a> {
+        CurrencyConverter {
+            reporting_currency,
+            account_currency,
+            rates,
+        }
+    }
+}
+
+impl<'a> Collate for CurrencyConverter<'a> {
+    fn collate(
+        &self,
+        mut transactions: Vec<Transaction>,
+        _: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
+        for transaction in &mut transactions {
+            let currency = transaction
+                .currency
+                .or_else(|| {
+                    self.account_currency
+                        .get(&transaction.account_name)
+                        .cloned()
+                })
+                .unwrap_or(self.reporting_currency);
+            if currency != self.reporting_currency {
+                let rate = self
+                    .rates
+                    .rate(currency, self.reporting_currency, transaction.date)
+                    .ok_or_else(|| BudgetError::NoExchangeRate {
+                        from: currency.to_string(),
+                        to: self.reporting_currency.to_string(),
+                        date: transaction.date,
+                    })?;
+                transaction.amount = transaction.amount * rate;
+                for split in &mut transaction.splits {
+                    split.amount = split.amount * rate;
+                }
+                transaction.balance = transaction.balance.map(|balance| balance * rate);
+                let rule = format!("{}/{} = {}", currency, self.reporting_currency, rate);
+                let amount = transaction.amount.to_string();
+                transaction.record("Currencies", rule.clone(), "amount", amount);
+                transaction.record(
+                    "Currencies",
+                    rule,
+                    "currency",
+                    self.reporting_currency.to_string(),
+                );
+            }
+            transaction.currency = Some(self.reporting_currency);
+        }
+        Ok(transactions)
+    }
+}
+
+/// Reports add up amounts without looking at their currency, so refuse to
+/// hand them transactions in more than one. A transaction without a currency
+/// could be in any, so it only goes with others that have none either.
+pub fn check_single_currency(transactions: &[Transaction]) -> BResult<()> {
+    let currencies = transactions
+        .iter()
+        .map(|t| t.currency)
+        .collect::<BTreeSet<_>>();
+    if currencies.len() > 1 {
+        Err(BudgetError::MixedCurrencies(
+            currencies
+                .iter()
+                .map(|c| c.map_or_else(|| "unknown".to_owned(), |c| c.to_string()))
+                .collect(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::loading::Money};
+
+    fn currency(code: &str) -> Currency {
+        code.parse().unwrap()
+    }
+
+    fn rates() -> ExchangeRates {
+        ExchangeRates::from_reader(
+            &mut "date,pair,rate\n2019-01-01,EUR/USD,1.25\n2/1/2019,EUR/USD,1.1\n".as_bytes(),
+        )
+        .expect("Unable to load rates")
+    }
+
+    #[test]
+    fn rate_lookup() {
+        let rates = rates();
+        let (eur, usd) = (currency("EUR"), currency("USD"));
+        assert_eq!(rates.rate(eur, usd, Date::ymd(2018, 12, 31)), None);
+        assert_eq!(rates.rate(eur, usd, Date::ymd(2019, 1, 15)), Some(1.25));
+        assert_eq!(rates.rate(eur, usd, Date::ymd(2019, 3, 1)), Some(1.1));
+        assert_eq!(rates.rate(usd, eur, Date::ymd(2019, 1, 15)), Some(0.8));
+        assert_eq!(rates.rate(usd, usd, Date::ymd(2018, 1, 1)), Some(1.0));
+    }
+
+    #[test]
+    fn converts_by_account() {
+        let mut account_currency = HashMap::new();
+        account_currency.insert("Girokonto".to_owned(), currency("EUR"));
+        let converter = CurrencyConverter::new(currency("USD"), &account_currency, rates());
+
+        let transactions = converter
+            .collate(
+                vec![
+                    Transaction {
+                        date: Date::ymd(2019, 1, 15),
+                        amount: Money::from_i64(10),
+                        balance: Some(Money::from_i64(100)),
+                        account_name: "Girokonto".to_owned(),
+                        ..Default::default()
+                    },
+                    Transaction {
+                        date: Date::ymd(2019, 1, 15),
+                        amount: Money::from_i64(10),
+                        account_name: "Checking".to_owned(),
+                        ..Default::default()
+                    },
+                ],
+                &mut Vec::new(),
+            )
+            .expect("Unable to convert");
+
+        assert_eq!(transactions[0].amount, "12.50".parse().unwrap());
+        assert_eq!(transactions[0].balance, Some(Money::from_i64(125)));
+        assert_eq!(transactions[1].amount, Money::from_i64(10));
+        assert_eq!(transactions[0].provenance.len(), 2);
+        assert!(transactions[1].provenance.is_empty());
+        assert!(transactions
+            .iter()
+            .all(|t| t.currency == Some(currency("USD"))));
+
+        assert!(converter
+            .collate(
+                vec![Transaction {
+                    date: Date::ymd(2018, 1, 1),
+                    account_name: "Girokonto".to_owned(),
+                    ..Default::default()
+                }],
+                &mut Vec::new()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn mixed_currencies_are_rejected() {
+        let in_currency = |code| Transaction {
+            currency: Some(currency(code)),
+            ..Default::default()
+        };
+        assert!(check_single_currency(&[in_currency("USD"), in_currency("USD")]).is_ok());
+        assert!(check_single_currency(&[Transaction::default(), Transaction::default()]).is_ok());
+        assert!(check_single_currency(&[in_currency("USD"), Transaction::default()]).is_err());
+        assert!(check_single_currency(&[in_currency("USD"), in_currency("EUR")]).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Transaction, TransactionType},
+        processing::CollatedTransactions,
+    },
+    std::{
+        collections::{BTreeSet, HashMap},
+        fmt,
+    },
+};
+
+/// Whether a transaction made it through processing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Visibility {
+    Shown,
+    Dropped {
+        processor: String,
+        reason: String,
+    },
+    /// Processing did not produce this transaction at all.
+    Missing,
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Visibility::Shown => write!(f, "shown"),
+            Visibility::Dropped {
+                ref processor,
+                ref reason,
+            } => write!(f, "dropped by {} ({})", processor, reason),
+            Visibility::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    Category {
+        before: String,
+        after: String,
+    },
+    Person {
+        before: String,
+        after: String,
+    },
+    Tags {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// The account a transfer goes to, if the transaction is a transfer.
+    Transfer {
+        before: Option<String>,
+        after: Option<String>,
+    },
+    Visibility {
+        before: Visibility,
+        after: Visibility,
+    },
+}
+
+fn or_none(value: &str) -> &str {
+    if value.is_empty() {
+        "(none)"
+    } else {
+        value
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Difference::Category {
+                ref before,
+                ref after,
+            } => write!(f, "category: {} -> {}", or_none(before), or_none(after)),
+            Difference::Person {
+                ref before,
+                ref after,
+            } => write!(f, "person: {} -> {}", or_none(before), or_none(after)),
+            Difference::Tags {
+                ref added,
+                ref removed,
+            } => {
+                write!(f, "tags:")?;
+                for tag in added {
+                    write!(f, " +{}", tag)?;
+                }
+                for tag in removed {
+                    write!(f, " -{}", tag)?;
+                }
+                Ok(())
+            }
+            Difference::Transfer {
+                ref before,
+                ref after,
+            } => write!(
+                f,
+                "transfer to: {} -> {}",
+                before.as_ref().map_or("(none)", String::as_str),
+                after.as_ref().map_or("(none)", String::as_str)
+            ),
+            Difference::Visibility {
+                ref before,
+                ref after,
+            } => write!(f, "{} -> {}", before, after),
+        }
+    }
+}
+
+/// Everything two configurations did differently to one transaction.
+#[derive(Debug)]
+pub struct TransactionDiff {
+    /// The transaction as the second configuration left it, unless it was
+    /// missing there.
+    pub transaction: Transaction,
+    pub differences: Vec<Difference>,
+}
+
+impl fmt::Display for TransactionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({})",
+            self.transaction.date,
+            self.transaction.original_description,
+            self.transaction.amount,
+            self.transaction.account_name
+        )?;
+        for difference in &self.differences {
+            write!(f, "\n    {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+fn by_uid(collated: &CollatedTransactions) -> HashMap<String, (&Transaction, Visibility)> {
+    let mut transactions = HashMap::new();
+    for t in &collated.transactions {
+        transactions.insert(t.uid(), (t, Visibility::Shown));
+    }
+    for d in &collated.dropped {
+        transactions.insert(
+            d.transaction.uid(),
+            (
+                &d.transaction,
+                Visibility::Dropped {
+                    processor: d.processor.clone(),
+                    reason: d.reason.clone(),
+                },
+            ),
+        );
+    }
+    transactions
+}
+
+fn transfer_destination(t: &Transaction) -> Option<String> {
+    match t.transaction_type {
+        TransactionType::Transfer => t.transfer_destination_account.clone(),
+        _ => None,
+    }
+}
+
+/// Who owns the transaction: its person, or its owner shares if it has them.
+fn owners(t: &Transaction) -> String {
+    if t.owner_shares.is_empty() {
+        t.person.clone()
+    } else {
+        t.owner_shares
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+fn differences(before: &Transaction, after: &Transaction) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    if before.category != after.category {
+        differences.push(Difference::Category {
+            before: before.category.clone(),
+            after: after.category.clone(),
+        });
+    }
+    let (before_owners, after_owners) = (owners(before), owners(after));
+    if before_owners != after_owners {
+        differences.push(Difference::Person {
+            before: before_owners,
+            after: after_owners,
+        });
+    }
+    let before_tags = before.tags.iter().collect::<BTreeSet<_>>();
+    let after_tags = after.tags.iter().collect::<BTreeSet<_>>();
+    if before_tags != after_tags {
+        differences.push(Difference::Tags {
+            added: after_tags
+                .difference(&before_tags)
+                .map(|t| (*t).clone())
+                .collect(),
+            removed: before_tags
+                .difference(&after_tags)
+                .map(|t| (*t).clone())
+                .collect(),
+        });
+    }
+    let (before_transfer, after_transfer) =
+        (transfer_destination(before), transfer_destination(after));
+    if before_transfer != after_transfer {
+        differences.push(Difference::Transfer {
+            before: before_transfer,
+            after: after_transfer,
+        });
+    }
+    differences
+}
+
+/// Compares what two runs of the processors did to the same transactions.
+/// Transactions are matched by id. The category, person, tags and transfers
+/// of transactions are only compared when both runs kept them, since a
+/// dropped transaction shows up in no report either way.
+pub fn diff(before: &CollatedTransactions, after: &CollatedTransactions) -> Vec<TransactionDiff> {
+    let before = by_uid(before);
+    let mut after = by_uid(after);
+
+    let mut diffs = Vec::new();
+    for (uid, &(before_t, ref before_visibility)) in &before {
+        let (transaction, differences) = match after.remove(uid) {
+            Some((after_t, after_visibility)) => {
+                let differences = if *before_visibility == Visibility::Shown
+                    && after_visibility == Visibility::Shown
+                {
+                    self::differences(before_t, after_t)
+                } else if *before_visibility != after_visibility {
+                    vec![Difference::Visibility {
+                        before: before_visibility.clone(),
+                        after: after_visibility,
+                    }]
+                } else {
+                    Vec::new()
+                };
+                (after_t, differences)
+            }
+            None => (
+                before_t,
+                vec![Difference::Visibility {
+                    before: before_visibility.clone(),
+                    after: Visibility::Missing,
+                }],
+            ),
+        };
+        if !differences.is_empty() {
+            diffs.push(TransactionDiff {
+                transaction: transaction.clone(),
+                differences,
+            });
+        }
+    }
+    for &(after_t, ref after_visibility) in after.values() {
+        diffs.push(TransactionDiff {
+            transaction: after_t.clone(),
+            differences: vec![Difference::Visibility {
+                before: Visibility::Missing,
+                after: after_visibility.clone(),
+            }],
+        });
+    }
+
+    diffs.sort_by(|a, b| {
+        (a.transaction.date, a.transaction.uid()).cmp(&(b.transaction.date, b.transaction.uid()))
+    });
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            loading::Money,
+            processing::{Collate, ConfiguredProcessors},
+        },
+        budgetronlib::fintime::Date,
+    };
+
+    fn run(config: &str, transactions: Vec<Transaction>) -> CollatedTransactions {
+        let processors: ConfiguredProcessors =
+            toml::from_str(config).expect("Unable to parse processors");
+        let mut dropped = Vec::new();
+        let transactions = processors
+            .collate(transactions, &mut dropped)
+            .expect("Unable to process");
+        CollatedTransactions {
+            transactions,
+            dropped,
+        }
+    }
+
+    fn t(id: &str, description: &str, account: &str) -> Transaction {
+        Transaction {
+            id: id.to_owned(),
+            date: Date::ymd(2019, 6, 1),
+            description: description.to_owned(),
+            original_description: description.to_owned(),
+            account_name: account.to_owned(),
+            amount: Money::from_i64(5),
+            transaction_type: TransactionType::Debit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tag_and_visibility_changes() {
+        let transactions = vec![
+            t("1", "COFFEE", "Visa"),
+            t("2", "COFFEE", "Old Card"),
+            t("3", "GROCERIES", "Visa"),
+        ];
+        let before = run(
+            r#"
+            [[processor]]
+            [processor.tags.coffee]
+            description = ["COFFEE"]
+            "#,
+            transactions.clone(),
+        );
+        let after = run(
+            r#"
+            [[processor]]
+            hide_accounts = ["Old Card"]
+
+            [[processor]]
+            [processor.tags.coffee]
+            description = ["^COFFEE$", "GROCERIES"]
+            "#,
+            transactions,
+        );
+
+        let diffs = diff(&before, &after);
+        let summary = diffs
+            .iter()
+            .map(|d| (d.transaction.id.as_str(), d.differences.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![("2", 1), ("3", 1)]);
+        match diffs[0].differences[0] {
+            Difference::Visibility {
+                before: Visibility::Shown,
+                after: Visibility::Dropped { ref processor, .. },
+            } => assert_eq!(processor, "HideAccount"),
+            ref other => panic!("Unexpected difference {:?}", other),
+        }
+        assert_eq!(
+            diffs[1].differences[0],
+            Difference::Tags {
+                added: vec!["coffee".to_owned()],
+                removed: vec![],
+            }
+        );
+    }
+}
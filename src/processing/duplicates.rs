@@ -0,0 +1,196 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::Transaction,
+        processing::{config::TransactionMatcher, Collate, DroppedTransaction, Window},
+        reporting::RepeatedTransactions,
+    },
+    budgetronlib::error::BResult,
+    serde::Deserialize,
+    std::collections::{HashMap, HashSet},
+};
+
+/// How suspected duplicate charges are flagged.
+#[derive(Debug, Deserialize)]
+pub struct DuplicateOptions {
+    /// The tag added to every charge of a suspected duplicate.
+    #[serde(rename = "duplicate_tag", default = "default_duplicate_tag")]
+    pub tag: String,
+    /// Charges that are expected to repeat, like subscriptions, which are
+    /// never flagged.
+    #[serde(rename = "duplicate_exclude")]
+    pub exclude: Option<Box<TransactionMatcher>>,
+    /// How recurring charges are found, the same way the
+    /// "RepeatedTransactions" report does. They are never flagged either.
+    #[serde(rename = "duplicate_recurring", default)]
+    pub recurring: RepeatedTransactions,
+}
+
+pub fn default_duplicate_tag() -> String {
+    "possible-duplicate".to_owned()
+}
+
+/// Tags debits from the same merchant, for the same amount and in the same
+/// account, that are within the window of each other. Nothing is dropped,
+/// since either charge may turn out to be real.
+pub struct DuplicateCollator<'a> {
+    pub window: Window,
+    pub options: &'a DuplicateOptions,
+}
+
+impl<'a> DuplicateCollator<'a> {
+    pub fn new(window: Window, options: &'a DuplicateOptions) -> DuplicateCollator<'a> {
+        DuplicateCollator { window, options }
+    }
+
+    fn candidate(&self, t: &Transaction, recurring: &HashSet<String>) -> bool {
+        t.transaction_type.is_debit()
+            && self.options.exclude.iter().all(|m| !m.matches(t))
+            && !recurring.contains(&t.uid())
+    }
+}
+
+impl<'a> Collate for DuplicateCollator<'a> {
+    fn collate(
+        &self,
+        mut transactions: Vec<Transaction>,
+        _: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
+        let ends = self.window.ends(&transactions);
+        let merchants = transactions
+            .iter()
+            .map(Transaction::merchant)
+            .collect::<Vec<_>>();
+        let recurring = self.options.recurring.recurring_uids(&transactions);
+
+        // Each flagged charge, and the earliest charge it duplicates.
+        let mut flagged = HashMap::new();
+        for (i, t) in transactions.iter().enumerate() {
+            if !self.candidate(t, &recurring) || merchants[i].is_empty() {
+                continue;
+            }
+            for j in i + 1..ends[i] {
+                let other = &transactions[j];
+                if other.amount == t.amount
+                    && other.account_name == t.account_name
+                    && other.currency == t.currency
+                    && merchants[j] == merchants[i]
+                    && self.candidate(other, &recurring)
+                {
+                    let first = flagged.get(&i).cloned().unwrap_or(i);
+                    flagged.entry(i).or_insert(i);
+                    flagged.entry(j).or_insert(first);
+                }
+            }
+        }
+
+        let rule = self.window.rule("duplicate");
+        let tag = &self.options.tag;
+        for (i, first) in flagged {
+            let original = transactions[first].uid();
+            let transaction = &mut transactions[i];
+            if transaction.tags.contains(tag) {
+                continue;
+            }
+            transaction.tags.push(tag.clone());
+            let rule = if i == first {
+                rule.clone()
+            } else {
+                format!("{} (duplicates {})", rule, original)
+            };
+            transaction.record("DuplicateCharges", rule, "tags", tag.clone());
+        }
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::loading::{Money, TransactionType},
+        budgetronlib::fintime::Date,
+    };
+
+    fn charge(day: i32, description: &str, amount: i64, account: &str) -> Transaction {
+        Transaction {
+            id: format!("{}-{}", description, day),
+            date: Date::ymd(2019, 3, day),
+            description: description.to_owned(),
+            original_description: description.to_owned(),
+            amount: Money::from_i64(amount),
+            transaction_type: TransactionType::Debit,
+            account_name: account.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn flags_duplicates() {
+        let transactions = vec![
+            charge(1, "HOTEL CALIFORNIA #12", 240, "Visa"),
+            charge(1, "NETFLIX.COM", 13, "Visa"),
+            charge(2, "HOTEL CALIFORNIA #13", 240, "Visa"),
+            charge(2, "NETFLIX.COM", 13, "Visa"),
+            charge(3, "HOTEL CALIFORNIA #12", 240, "Checking"),
+            charge(3, "COFFEE", 4, "Visa"),
+            charge(3, "COFFEE", 5, "Visa"),
+            charge(20, "HOTEL CALIFORNIA #12", 240, "Visa"),
+        ];
+        let options: DuplicateOptions =
+            toml::from_str(r#"duplicate_exclude = { description = ["NETFLIX"] }"#).unwrap();
+        let transactions = DuplicateCollator::new(Window::Days(5), &options)
+            .collate(transactions, &mut Vec::new())
+            .unwrap();
+
+        let flagged = transactions
+            .iter()
+            .filter(|t| t.tags.contains(&"possible-duplicate".to_owned()))
+            .map(|t| t.uid())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flagged,
+            vec!["HOTEL CALIFORNIA #12-1", "HOTEL CALIFORNIA #13-2"]
+        );
+        assert_eq!(
+            transactions[2].provenance[0].rule,
+            "duplicate_window = 5 (duplicates HOTEL CALIFORNIA #12-1)"
+        );
+    }
+
+    #[test]
+    fn skips_recurring() {
+        // A gym that charges on the 1st of every month, and once more the
+        // day after in March.
+        let mut transactions = (1..=5)
+            .map(|month| {
+                let mut t = charge(1, "GYM", 40, "Visa");
+                t.id = format!("GYM-{}", month);
+                t.date = Date::ymd(2019, month, 1);
+                t
+            })
+            .collect::<Vec<_>>();
+        transactions.push(charge(2, "GYM", 40, "Visa"));
+        transactions.push(charge(2, "HOTEL CALIFORNIA", 240, "Visa"));
+        transactions.push(charge(3, "HOTEL CALIFORNIA", 240, "Visa"));
+        transactions.sort_by_key(|t| t.date);
+
+        let options: DuplicateOptions = toml::from_str("").unwrap();
+        let transactions = DuplicateCollator::new(Window::Days(5), &options)
+            .collate(transactions, &mut Vec::new())
+            .unwrap();
+        let flagged = transactions
+            .iter()
+            .filter(|t| !t.tags.is_empty())
+            .map(|t| t.uid())
+            .collect::<Vec<_>>();
+        assert_eq!(flagged, vec!["HOTEL CALIFORNIA-2", "HOTEL CALIFORNIA-3"]);
+    }
+}
@@ -6,11 +6,25 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use {crate::loading::Transaction, budgetronlib::error::BResult};
+use {
+    crate::loading::{ensure_unique_ids, Transaction},
+    budgetronlib::error::BResult,
+    serde::Serialize,
+};
 
+mod amortize;
 pub mod config;
+mod currency;
+mod diff;
+mod duplicates;
+mod pairing;
+mod reconcile;
 mod refunds;
 mod regex;
+mod rewrite;
+mod shares;
+mod split;
+mod suggest;
 mod transfers;
 
 pub enum Collator {
@@ -20,19 +34,106 @@ pub enum Collator {
 }
 
 pub use crate::processing::{
-    config::ConfiguredProcessors, refunds::RefundCollator, transfers::TransferCollator,
+    amortize::AmortizeRule,
+    config::ConfiguredProcessors,
+    currency::{check_single_currency, CurrencyConverter, ExchangeRates},
+    diff::{diff, Difference, TransactionDiff, Visibility},
+    duplicates::{default_duplicate_tag, DuplicateCollator, DuplicateOptions},
+    reconcile::{reconcile, BalanceAssertion, BalanceSource, ConfiguredBalances, Divergence},
+    refunds::{PartialRefundCollator, RefundCollator},
+    rewrite::RewriteRule,
+    shares::ShareRule,
+    split::SplitRule,
+    suggest::{CategoryClassifier, SuggestOptions, Suggestion, SUGGEST_CATEGORIES},
+    transfers::{TransferCollator, TransferOptions},
 };
 
+/// How far apart a debit and a credit may be to be paired up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    /// At most this many transactions apart.
+    Transactions(usize),
+    /// Posted at most this many days apart.
+    Days(i64),
+}
+
+impl Window {
+    /// For every transaction, the index just past the last transaction
+    /// within the window after it. Day windows expect the transactions to be
+    /// sorted by date, as loading leaves them.
+    fn ends(self, transactions: &[Transaction]) -> Vec<usize> {
+        match self {
+            Window::Transactions(horizon) => (0..transactions.len())
+                .map(|i| (i + horizon).min(transactions.len()))
+                .collect(),
+            Window::Days(days) => {
+                let mut end = 0;
+                transactions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| {
+                        end = end.max(i);
+                        while end < transactions.len() && transactions[end].date - t.date <= days {
+                            end += 1;
+                        }
+                        end
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// How the window was configured, e.g. `refund_horizon = 100`.
+    fn rule(self, prefix: &str) -> String {
+        match self {
+            Window::Transactions(horizon) => format!("{}_horizon = {}", prefix, horizon),
+            Window::Days(days) => format!("{}_window = {}", prefix, days),
+        }
+    }
+}
+
+/// A transaction that a processor removed, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct DroppedTransaction {
+    pub transaction: Transaction,
+    pub processor: String,
+    pub reason: String,
+}
+
+impl DroppedTransaction {
+    pub fn new(transaction: Transaction, processor: &str, reason: String) -> DroppedTransaction {
+        DroppedTransaction {
+            transaction,
+            processor: processor.to_owned(),
+            reason,
+        }
+    }
+}
+
+pub struct CollatedTransactions {
+    pub transactions: Vec<Transaction>,
+    pub dropped: Vec<DroppedTransaction>,
+}
+
 pub trait Collate {
-    fn collate(&self, transactions: Vec<Transaction>) -> BResult<Vec<Transaction>>;
+    /// Processes the transactions, adding any that are removed to `dropped`.
+    fn collate(
+        &self,
+        transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>>;
 }
 
 impl Collate for Collator {
-    fn collate(&self, transactions: Vec<Transaction>) -> BResult<Vec<Transaction>> {
+    fn collate(
+        &self,
+        transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
         match *self {
-            Collator::Transfers(ref tc) => tc.collate(transactions),
-            Collator::Config(ref cfg) => cfg.collate(transactions),
-            Collator::Refund(ref rc) => rc.collate(transactions),
+            Collator::Transfers(ref tc) => tc.collate(transactions, dropped),
+            Collator::Config(ref cfg) => cfg.collate(transactions, dropped),
+            Collator::Refund(ref rc) => rc.collate(transactions, dropped),
         }
     }
 }
@@ -40,9 +141,16 @@ impl Collate for Collator {
 pub fn collate_all(
     mut transactions: Vec<Transaction>,
     collators: &[Collator],
-) -> BResult<Vec<Transaction>> {
+) -> BResult<CollatedTransactions> {
+    let mut dropped = Vec::new();
     for collator in collators {
-        transactions = collator.collate(transactions)?
+        transactions = collator.collate(transactions, &mut dropped)?
     }
-    Ok(transactions)
+    // Transfer fees and amortized portions get ids made from another
+    // transaction's, which may already be taken.
+    ensure_unique_ids(&mut transactions);
+    Ok(CollatedTransactions {
+        transactions,
+        dropped,
+    })
 }
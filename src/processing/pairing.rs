@@ -0,0 +1,385 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Money, Transaction},
+        processing::Window,
+    },
+    std::collections::BTreeMap,
+};
+
+/// Pairs up debits with credits, the way transfers and refunds are found.
+///
+/// Each transaction in turn looks at the unpaired transactions from itself to
+/// the end of its window whose amounts are within tolerance of its own. Like
+/// `pairs`, the tolerance is that of the debit's amount, or of the
+/// transaction's own when both are of the same kind.
+/// Among those, the debit and credit that `pairs` accepts with the closest
+/// amounts, and then the closest dates, are paired up. That repeats until the
+/// transaction itself is paired or nothing else pairs. Amounts in different
+/// currencies are never compared, so a debit and a credit only pair up when
+/// their currencies are the same.
+///
+/// Candidates are looked up in an index by amount, within the largest
+/// tolerance of any amount, so this takes about linear time however long the
+/// window is. Returns (debit, credit) index pairs.
+pub fn pair_up<T, P>(
+    transactions: &[Transaction],
+    window: Window,
+    tolerance: T,
+    pairs: P,
+) -> Vec<(usize, usize)>
+where
+    T: Fn(Money) -> Money,
+    P: Fn(&Transaction, &Transaction) -> bool,
+{
+    let mut by_amount: BTreeMap<Money, Vec<usize>> = BTreeMap::new();
+    for (i, t) in transactions.iter().enumerate() {
+        by_amount.entry(t.amount).or_default().push(i);
+    }
+    let ends = window.ends(transactions);
+    let widest = transactions
+        .iter()
+        .map(|t| tolerance(t.amount))
+        .max()
+        .unwrap_or_else(Money::zero);
+    let within = |a: &Transaction, b: &Transaction| {
+        let debit = if b.transaction_type.is_debit() && !a.transaction_type.is_debit() {
+            b
+        } else {
+            a
+        };
+        (a.amount - b.amount).abs() <= tolerance(debit.amount)
+    };
+
+    let mut paired = vec![false; transactions.len()];
+    let mut found = Vec::new();
+    let mut candidates = Vec::new();
+    for (i, t) in transactions.iter().enumerate() {
+        while !paired[i] {
+            candidates.clear();
+            for indices in by_amount
+                .range(t.amount - widest..=t.amount + widest)
+                .map(|(_, indices)| indices)
+            {
+                let start = match indices.binary_search(&i) {
+                    Ok(start) | Err(start) => start,
+                };
+                candidates.extend(
+                    indices[start..]
+                        .iter()
+                        .cloned()
+                        .take_while(|&j| j < ends[i])
+                        .filter(|&j| !paired[j] && within(t, &transactions[j])),
+                );
+            }
+
+            if candidates.len() <= 1 {
+                break;
+            }
+            candidates.sort();
+
+            let mut closest = None;
+            let debits = candidates
+                .iter()
+                .filter(|&&j| transactions[j].transaction_type.is_debit());
+            for &debit_ix in debits {
+                let debit = &transactions[debit_ix];
+                let credits = candidates
+                    .iter()
+                    .filter(|&&j| transactions[j].transaction_type.is_credit());
+                for &credit_ix in credits {
+                    let credit = &transactions[credit_ix];
+                    let delta = (
+                        (debit.amount - credit.amount).abs(),
+                        (debit.date - credit.date).abs(),
+                    );
+                    if closest.iter().all(|&(_, mindelta)| delta < mindelta)
+                        && debit.currency == credit.currency
+                        && pairs(debit, credit)
+                    {
+                        closest = Some(((debit_ix, credit_ix), delta));
+                    }
+                }
+            }
+
+            match closest {
+                Some(((debit_ix, credit_ix), _)) => {
+                    paired[debit_ix] = true;
+                    paired[credit_ix] = true;
+                    found.push((debit_ix, credit_ix));
+                }
+                None => break,
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            loading::TransactionType,
+            processing::{Collate, RefundCollator, TransferCollator},
+        },
+        budgetronlib::fintime::{Date, Timeframe},
+        std::{
+            cmp::min,
+            collections::{HashMap, HashSet},
+        },
+    };
+
+    /// `TransferCollator::collate` as it was before pairing went through an
+    /// index, rescanning the horizon of every transaction.
+    fn transfers_by_scanning(
+        mut transactions: Vec<Transaction>,
+        horizon: usize,
+    ) -> Vec<Transaction> {
+        let mut to_delete = HashSet::new();
+        let mut to_update = HashMap::new();
+        for (i, t) in transactions.iter().enumerate() {
+            loop {
+                let candidates: Vec<_> = (i..min(transactions.len(), i + horizon))
+                    .filter(|&j| {
+                        let tn = &transactions[j];
+                        tn.amount == t.amount
+                            && !to_delete.contains(&i)
+                            && !to_delete.contains(&j)
+                            && !to_update.contains_key(&i)
+                            && !to_update.contains_key(&j)
+                    })
+                    .collect();
+
+                if candidates.len() <= 1 {
+                    break;
+                }
+
+                let mut mindelta = i64::MAX;
+                let mut found_transfer = (0, 0);
+                let debits = candidates
+                    .iter()
+                    .filter(|&i| transactions[*i].transaction_type.is_debit());
+
+                for debit_ix in debits {
+                    let debit = &transactions[*debit_ix];
+                    let credits = candidates
+                        .iter()
+                        .filter(|&i| transactions[*i].transaction_type.is_credit());
+                    for credit_ix in credits {
+                        let credit = &transactions[*credit_ix];
+                        if (debit.date - credit.date).abs() < mindelta
+                            && debit.account_name != credit.account_name
+                        {
+                            found_transfer = (*debit_ix, *credit_ix);
+                            mindelta = (debit.date - credit.date).abs();
+                        }
+                    }
+                }
+
+                if found_transfer != (0, 0) {
+                    let tn = &transactions[found_transfer.1];
+
+                    to_delete.insert(found_transfer.1);
+                    to_update.insert(found_transfer.0, tn.account_name.clone());
+
+                    if found_transfer.0 == i || found_transfer.1 == i {
+                        break;
+                    }
+                } else {
+                    break;
+                }
+            }
+        }
+
+        for (i, destination_account) in to_update {
+            if let Some(transaction) = transactions.get_mut(i) {
+                transaction.transfer_destination_account = Some(destination_account);
+                transaction.transaction_type = TransactionType::Transfer;
+            }
+        }
+
+        let mut to_delete: Vec<_> = to_delete.into_iter().collect();
+        to_delete.sort();
+        to_delete.reverse();
+
+        for i in to_delete {
+            transactions.remove(i);
+        }
+        transactions
+    }
+
+    /// `RefundCollator::collate` as it was before pairing went through an
+    /// index.
+    fn refunds_by_scanning(mut transactions: Vec<Transaction>, horizon: usize) -> Vec<Transaction> {
+        let mut to_delete = HashSet::new();
+        for (i, t) in transactions.iter().enumerate() {
+            loop {
+                let candidates: Vec<_> = (i..min(transactions.len(), i + horizon))
+                    .filter(|&j| {
+                        let tn = &transactions[j];
+                        tn.amount == t.amount && !to_delete.contains(&i) && !to_delete.contains(&j)
+                    })
+                    .collect();
+
+                if candidates.len() <= 1 {
+                    break;
+                }
+
+                let mut mindelta = i64::MAX;
+                let mut found_transfer = (0, 0);
+                let debits = candidates
+                    .iter()
+                    .filter(|&i| transactions[*i].transaction_type.is_debit());
+
+                for debit_ix in debits {
+                    let debit = &transactions[*debit_ix];
+                    let credits = candidates
+                        .iter()
+                        .filter(|&i| transactions[*i].transaction_type.is_credit());
+
+                    for credit_ix in credits {
+                        let credit = &transactions[*credit_ix];
+                        if (debit.date - credit.date).abs() < mindelta
+                            && debit.account_name == credit.account_name
+                        {
+                            found_transfer = (*debit_ix, *credit_ix);
+                            mindelta = (debit.date - credit.date).abs();
+                        }
+                    }
+                }
+
+                if found_transfer != (0, 0) {
+                    to_delete.insert(found_transfer.1);
+                    to_delete.insert(found_transfer.0);
+
+                    if found_transfer.0 == i || found_transfer.1 == i {
+                        break;
+                    }
+                } else {
+                    break;
+                }
+            }
+        }
+
+        let mut to_delete: Vec<_> = to_delete.into_iter().collect();
+        to_delete.sort();
+        to_delete.reverse();
+
+        for i in to_delete {
+            transactions.remove(i);
+        }
+
+        transactions
+    }
+
+    /// A few thousand transactions over a handful of accounts and amounts, so
+    /// that plenty of them could pair up in more than one way.
+    fn history() -> Vec<Transaction> {
+        let mut seed: u64 = 42;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+        let accounts = ["Checking", "Savings", "Visa"];
+        let mut day = 0;
+        (0..3000)
+            .map(|i| {
+                day += next(2) as i64;
+                Transaction {
+                    id: format!("t{}", i),
+                    date: Date::ymd(2015, 1, 1) + Timeframe::Days(day),
+                    amount: Money::from_i64(next(20) as i64 + 1),
+                    transaction_type: if next(2) == 0 {
+                        TransactionType::Debit
+                    } else {
+                        TransactionType::Credit
+                    },
+                    account_name: accounts[next(3) as usize].to_owned(),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    fn summary(transactions: &[Transaction]) -> Vec<(String, TransactionType, Option<String>)> {
+        transactions
+            .iter()
+            .map(|t| {
+                (
+                    t.id.clone(),
+                    t.transaction_type,
+                    t.transfer_destination_account.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_scanning() {
+        let transactions = history();
+        for &horizon in &[2, 10, 100] {
+            let transfers = TransferCollator::new(horizon)
+                .collate(transactions.clone(), &mut Vec::new())
+                .unwrap();
+            assert_eq!(
+                summary(&transfers),
+                summary(&transfers_by_scanning(transactions.clone(), horizon))
+            );
+            let refunds = RefundCollator::new(horizon)
+                .collate(transactions.clone(), &mut Vec::new())
+                .unwrap();
+            assert_eq!(
+                summary(&refunds),
+                summary(&refunds_by_scanning(transactions.clone(), horizon))
+            );
+        }
+    }
+
+    #[test]
+    fn tolerance_of_the_debit() {
+        let t = |amount: &str, transaction_type, account: &str| Transaction {
+            amount: amount.parse().unwrap(),
+            transaction_type,
+            account_name: account.to_owned(),
+            ..Default::default()
+        };
+        // 4% of the debit covers the difference, but 4% of the credit does not.
+        let transactions = [
+            t("100", TransactionType::Credit, "Savings"),
+            t("104.10", TransactionType::Debit, "Checking"),
+        ];
+        let pairs = pair_up(
+            &transactions,
+            Window::Transactions(10),
+            |amount| amount.abs() * 0.04,
+            |debit, credit| (debit.amount - credit.amount).abs() <= debit.amount * 0.04,
+        );
+        assert_eq!(pairs, vec![(1, 0)]);
+    }
+
+    #[test]
+    fn day_windows() {
+        let transactions = history();
+        let pairs = pair_up(
+            &transactions,
+            Window::Days(3),
+            |_| Money::zero(),
+            |_, _| true,
+        );
+        assert!(!pairs.is_empty());
+        let mut seen = HashMap::new();
+        for &(debit, credit) in &pairs {
+            assert!((transactions[debit].date - transactions[credit].date).abs() <= 3);
+            assert!(seen.insert(debit, credit).is_none());
+            assert!(seen.insert(credit, debit).is_none());
+        }
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Money, Transaction, TransactionType},
+        processing::DroppedTransaction,
+    },
+    budgetronlib::fintime::Date,
+    serde::Deserialize,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt,
+        ops::Bound::{Excluded, Included},
+    },
+};
+
+/// A balance the user knows an account had at the end of a day, e.g. from a
+/// paper statement.
+#[derive(Debug, Deserialize)]
+pub struct BalanceAssertion {
+    account: String,
+    date: Date,
+    balance: Money,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ConfiguredBalances {
+    #[serde(default)]
+    balance: Vec<BalanceAssertion>,
+}
+
+impl ConfiguredBalances {
+    pub fn assertions(&self) -> &[BalanceAssertion] {
+        &self.balance
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BalanceSource {
+    Statement,
+    Assertion,
+}
+
+/// A day on which the balance computed from an account's transactions does
+/// not match the balance its bank reported or the user asserted.
+#[derive(Debug)]
+pub struct Divergence {
+    pub account: String,
+    pub date: Date,
+    pub expected: Money,
+    pub computed: Money,
+    pub source: BalanceSource,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} on {}: {} balance is {} but transactions add up to {} (off by {})",
+            self.account,
+            self.date,
+            match self.source {
+                BalanceSource::Statement => "statement",
+                BalanceSource::Assertion => "asserted",
+            },
+            self.expected,
+            self.computed,
+            self.computed - self.expected
+        )
+    }
+}
+
+struct Entry {
+    date: Date,
+    change: Money,
+    balance: Option<Money>,
+}
+
+/// A balance to compare against, along with the running total of the
+/// account's transactions at that point, relative to an unknown opening
+/// balance.
+struct Checkpoint {
+    date: Date,
+    expected: Money,
+    relative: Money,
+    source: BalanceSource,
+}
+
+fn entries(transactions: &[Transaction]) -> BTreeMap<String, Vec<Entry>> {
+    let mut accounts: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+    for t in transactions {
+        let change = match t.transaction_type {
+            TransactionType::Credit => t.amount,
+            TransactionType::Debit | TransactionType::Transfer => -t.amount,
+        };
+        accounts
+            .entry(t.account_name.clone())
+            .or_default()
+            .push(Entry {
+                date: t.date,
+                change,
+                balance: t.balance,
+            });
+        if let (TransactionType::Transfer, Some(ref destination)) =
+            (t.transaction_type, &t.transfer_destination_account)
+        {
+            accounts
+                .entry(destination.clone())
+                .or_default()
+                .push(Entry {
+                    date: t.date,
+                    change: t.amount,
+                    balance: None,
+                });
+        }
+    }
+    for entries in accounts.values_mut() {
+        entries.sort_by_key(|e| e.date);
+    }
+    accounts
+}
+
+/// Whether the transactions within each day are listed newest first. Exports
+/// differ, and the order within a day survives loading, so look at which
+/// order makes neighbouring balances agree more often.
+fn newest_first(entries: &[Entry]) -> bool {
+    let (mut oldest_first_votes, mut newest_first_votes) = (0, 0);
+    for pair in entries.windows(2) {
+        if let (Some(before), Some(after)) = (pair[0].balance, pair[1].balance) {
+            if pair[0].date != pair[1].date {
+                continue;
+            }
+            if after == before + pair[1].change {
+                oldest_first_votes += 1;
+            }
+            if before == after + pair[0].change {
+                newest_first_votes += 1;
+            }
+        }
+    }
+    newest_first_votes > oldest_first_votes
+}
+
+fn statement_checkpoints(entries: &[Entry], checkpoints: &mut Vec<Checkpoint>) {
+    let newest_first = newest_first(entries);
+    let mut running = Money::zero();
+    let mut start = 0;
+    while start < entries.len() {
+        let date = entries[start].date;
+        let day = entries[start..]
+            .iter()
+            .take_while(|e| e.date == date)
+            .collect::<Vec<_>>();
+        for (i, entry) in day.iter().enumerate() {
+            if let Some(balance) = entry.balance {
+                // The balance after this transaction includes every transaction
+                // of the day that happened before it.
+                let happened = if newest_first { &day[i..] } else { &day[..=i] };
+                checkpoints.push(Checkpoint {
+                    date,
+                    expected: balance,
+                    relative: running + happened.iter().map(|e| e.change).sum(),
+                    source: BalanceSource::Statement,
+                });
+            }
+        }
+        running += day.iter().map(|e| e.change).sum();
+        start += day.len();
+    }
+}
+
+fn assertion_checkpoints(
+    entries: &[Entry],
+    assertions: &[&BalanceAssertion],
+    checkpoints: &mut Vec<Checkpoint>,
+) {
+    for assertion in assertions {
+        checkpoints.push(Checkpoint {
+            date: assertion.date,
+            expected: assertion.balance,
+            relative: entries
+                .iter()
+                .filter(|e| e.date <= assertion.date)
+                .map(|e| e.change)
+                .sum(),
+            source: BalanceSource::Assertion,
+        });
+    }
+}
+
+/// Days on which processing changed an account's transactions so that they
+/// no longer add up to what the bank saw: transactions were dropped, or their
+/// amounts were converted, refunded or spread over other days. Transfers are
+/// left alone, since the merged transfer and its fee move the same money as
+/// the two halves did.
+fn changed_days(
+    transactions: &[Transaction],
+    dropped: &[DroppedTransaction],
+) -> BTreeMap<String, BTreeSet<Date>> {
+    let mut changed: BTreeMap<String, BTreeSet<Date>> = BTreeMap::new();
+    let dropped = dropped
+        .iter()
+        .map(|d| (&d.transaction, d.processor != "Transfers"));
+    for (t, dropped) in transactions.iter().map(|t| (t, false)).chain(dropped) {
+        let amount_changed = t
+            .provenance
+            .iter()
+            .any(|c| c.field == "amount" && c.processor != "Transfers");
+        if !dropped && !amount_changed {
+            continue;
+        }
+        changed
+            .entry(t.account_name.clone())
+            .or_default()
+            .insert(t.date);
+        if let Some(ref destination) = t.transfer_destination_account {
+            changed
+                .entry(destination.clone())
+                .or_default()
+                .insert(t.date);
+        }
+    }
+    changed
+}
+
+/// Recomputes every account's running balance and compares it with the
+/// balances reported alongside its transactions and with the user's
+/// assertions. The opening balance is taken from the earliest of those, and
+/// every later day that does not add up is reported once per source.
+///
+/// Credits add to an account and debits take away from it, so credit card
+/// balances should be given as negative numbers.
+///
+/// The transactions are the processed ones, along with those the processors
+/// dropped. Days that processing changed can not be checked, so balances on
+/// them are skipped and the opening balance is taken again from the next
+/// balance after them.
+pub fn reconcile(
+    transactions: &[Transaction],
+    dropped: &[DroppedTransaction],
+    assertions: &[BalanceAssertion],
+) -> Vec<Divergence> {
+    let changed = changed_days(transactions, dropped);
+    let no_days = BTreeSet::new();
+    let mut accounts = entries(transactions);
+    for assertion in assertions {
+        accounts.entry(assertion.account.clone()).or_default();
+    }
+
+    let mut divergences = Vec::new();
+    for (account, entries) in &accounts {
+        let mut checkpoints = Vec::new();
+        statement_checkpoints(entries, &mut checkpoints);
+        let account_assertions = assertions
+            .iter()
+            .filter(|a| &a.account == account)
+            .collect::<Vec<_>>();
+        assertion_checkpoints(entries, &account_assertions, &mut checkpoints);
+        checkpoints.sort_by_key(|c| c.date);
+
+        let changed = changed.get(account).unwrap_or(&no_days);
+        let mut opening = None;
+        let mut previous = None;
+        let mut reported: Vec<(Date, BalanceSource)> = Vec::new();
+        for checkpoint in &checkpoints {
+            let changed_since = match previous {
+                Some(previous) => changed
+                    .range((Excluded(previous), Included(checkpoint.date)))
+                    .next()
+                    .is_some(),
+                None => false,
+            };
+            previous = Some(checkpoint.date);
+            if changed.contains(&checkpoint.date) {
+                opening = None;
+                continue;
+            } else if changed_since {
+                opening = None;
+            }
+            let opening = *opening.get_or_insert(checkpoint.expected - checkpoint.relative);
+            let computed = opening + checkpoint.relative;
+            if computed == checkpoint.expected
+                || reported.contains(&(checkpoint.date, checkpoint.source))
+            {
+                continue;
+            }
+            reported.push((checkpoint.date, checkpoint.source));
+            divergences.push(Divergence {
+                account: account.clone(),
+                date: checkpoint.date,
+                expected: checkpoint.expected,
+                computed,
+                source: checkpoint.source,
+            });
+        }
+    }
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::processing::{Collate, ConfiguredProcessors},
+    };
+
+    fn t(day: i32, amount: i64, transaction_type: TransactionType, balance: i64) -> Transaction {
+        Transaction {
+            date: Date::ymd(2019, 1, day),
+            amount: Money::from_i64(amount),
+            transaction_type,
+            account_name: "Checking".to_owned(),
+            balance: Some(Money::from_i64(balance)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn consistent_statement() {
+        use self::TransactionType::*;
+        // Listed newest first within each day.
+        let transactions = vec![
+            t(1, 10, Debit, 80),
+            t(1, 10, Debit, 90),
+            t(2, 50, Credit, 130),
+        ];
+        assert!(reconcile(&transactions, &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn missing_transaction() {
+        use self::TransactionType::*;
+        let transactions = vec![
+            t(1, 10, Debit, 90),
+            // A 20 debit on the 2nd was never imported.
+            t(3, 5, Debit, 65),
+            t(4, 5, Debit, 60),
+        ];
+        let divergences = reconcile(&transactions, &[], &[]);
+        assert_eq!(divergences.len(), 2);
+        assert_eq!(divergences[0].date, Date::ymd(2019, 1, 3));
+        assert_eq!(divergences[0].computed, Money::from_i64(85));
+        assert_eq!(divergences[0].expected, Money::from_i64(65));
+    }
+
+    #[test]
+    fn after_processing() {
+        use self::TransactionType::*;
+        let mut transactions = vec![
+            t(1, 10, Debit, 90),
+            t(2, 20, Debit, 70),
+            t(3, 5, Debit, 65),
+            // A 10 debit on the 4th was never imported.
+            t(5, 5, Debit, 50),
+        ];
+        transactions[1].description = "ATM FEE".to_owned();
+
+        // Hiding the fee leaves a gap between the statement balances around
+        // it, so the balances start over after it.
+        let processors: ConfiguredProcessors = toml::from_str(
+            r#"
+            [[processor]]
+            hide_description = ["ATM FEE"]
+            "#,
+        )
+        .unwrap();
+        let mut dropped = Vec::new();
+        let processed = processors.collate(transactions, &mut dropped).unwrap();
+        let divergences = reconcile(&processed, &dropped, &[]);
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(divergences[0].date, Date::ymd(2019, 1, 5));
+        assert_eq!(divergences[0].computed, Money::from_i64(60));
+    }
+
+    #[test]
+    fn assertions() {
+        let mut transfer = t(2, 25, TransactionType::Transfer, 75);
+        transfer.transfer_destination_account = Some("Savings".to_owned());
+        let assertions: ConfiguredBalances = toml::from_str(
+            r#"
+            [[balance]]
+            account = "Savings"
+            date = "1/1/2019"
+            balance = 100.0
+
+            [[balance]]
+            account = "Savings"
+            date = "1/2/2019"
+            balance = 125.0
+
+            [[balance]]
+            account = "Checking"
+            date = "1/5/2019"
+            balance = 70.0
+            "#,
+        )
+        .unwrap();
+
+        let divergences = reconcile(&[transfer], &[], assertions.assertions());
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(divergences[0].account, "Checking");
+        assert_eq!(divergences[0].source, BalanceSource::Assertion);
+        assert_eq!(divergences[0].computed, Money::from_i64(75));
+    }
+}
@@ -7,81 +7,322 @@
 // except according to those terms.
 
 use {
-    crate::{loading::Transaction, processing::Collate},
+    crate::{
+        loading::{Money, Transaction},
+        processing::{pairing::pair_up, Collate, DroppedTransaction, Window},
+    },
     budgetronlib::error::BResult,
-    std::{cmp::min, collections::HashSet, i64},
+    std::collections::{HashMap, HashSet},
 };
 
 pub struct RefundCollator {
-    pub horizon: usize,
+    pub window: Window,
 }
 
 impl RefundCollator {
     pub fn new(horizon: usize) -> RefundCollator {
-        RefundCollator { horizon }
+        RefundCollator::within(Window::Transactions(horizon))
+    }
+
+    pub fn within(window: Window) -> RefundCollator {
+        RefundCollator { window }
     }
 }
 
-impl Collate for RefundCollator {
-    fn collate(&self, mut transactions: Vec<Transaction>) -> BResult<Vec<Transaction>> {
-        let mut to_delete = HashSet::new();
-        for (i, t) in transactions.iter().enumerate() {
-            loop {
-                let candidates: Vec<_> = (i..min(transactions.len(), i + self.horizon))
-                    .filter(|&j| {
-                        let tn = &transactions[j];
-                        tn.amount == t.amount && !to_delete.contains(&i) && !to_delete.contains(&j)
-                    })
-                    .collect();
-
-                if candidates.len() <= 1 {
-                    break;
-                }
+/// Nets credits against earlier purchases from the same merchant in the same
+/// account, for returns of part of an order. The purchase is booked at what
+/// was kept and the credit is dropped, so it is not counted as income.
+pub struct PartialRefundCollator {
+    /// The most days a refund may come after its purchase.
+    pub window: i64,
+    /// How much of the shorter description must also be in the longer one
+    /// for two transactions to be from the same merchant, from 0 to 1.
+    pub similarity: f64,
+}
 
-                let mut mindelta = i64::MAX;
-                let mut found_transfer = (0, 0);
-                let debits = candidates
-                    .iter()
-                    .filter(|&i| transactions[*i].transaction_type.is_debit());
+/// The share of the words of the shorter merchant that are also in the
+/// longer one.
+fn merchant_similarity(a: &str, b: &str) -> f64 {
+    let a = a.split_whitespace().collect::<HashSet<_>>();
+    let b = b.split_whitespace().collect::<HashSet<_>>();
+    let shortest = a.len().min(b.len());
+    if shortest == 0 {
+        0.0
+    } else {
+        a.intersection(&b).count() as f64 / shortest as f64
+    }
+}
 
-                for debit_ix in debits {
-                    let debit = &transactions[*debit_ix];
-                    let credits = candidates
-                        .iter()
-                        .filter(|&i| transactions[*i].transaction_type.is_credit());
-
-                    for credit_ix in credits {
-                        let credit = &transactions[*credit_ix];
-                        if (debit.date - credit.date).abs() < mindelta
-                            && debit.account_name == credit.account_name
-                        {
-                            found_transfer = (*debit_ix, *credit_ix);
-                            mindelta = (debit.date - credit.date).abs();
-                        }
-                    }
-                }
+impl PartialRefundCollator {
+    pub fn new(window: i64, similarity: f64) -> PartialRefundCollator {
+        PartialRefundCollator { window, similarity }
+    }
 
-                if found_transfer != (0, 0) {
-                    to_delete.insert(found_transfer.1);
-                    to_delete.insert(found_transfer.0);
+    /// The purchase a credit most likely refunds: the most similar debit in
+    /// the same account within the window that is still worth at least the
+    /// credit, and the most recent one among those.
+    fn purchase(&self, transactions: &[Transaction], credit_ix: usize) -> Option<usize> {
+        let credit = &transactions[credit_ix];
+        let merchant = credit.merchant();
+        let mut best: Option<(f64, usize)> = None;
+        for (debit_ix, debit) in transactions[..credit_ix].iter().enumerate().rev() {
+            if credit.date - debit.date > self.window {
+                break;
+            }
+            if !debit.transaction_type.is_debit()
+                || debit.account_name != credit.account_name
+                || debit.currency != credit.currency
+                || debit.amount < credit.amount
+            {
+                continue;
+            }
+            let similarity = merchant_similarity(&merchant, &debit.merchant());
+            if similarity >= self.similarity && best.iter().all(|&(most, _)| similarity > most) {
+                best = Some((similarity, debit_ix));
+            }
+        }
+        best.map(|(_, debit_ix)| debit_ix)
+    }
+}
 
-                    if found_transfer.0 == i || found_transfer.1 == i {
-                        break;
-                    }
-                } else {
-                    break;
+impl Collate for PartialRefundCollator {
+    fn collate(
+        &self,
+        mut transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
+        let rule = Window::Days(self.window).rule("partial_refund");
+        let mut reasons = HashMap::new();
+        for credit_ix in 0..transactions.len() {
+            if !transactions[credit_ix].transaction_type.is_credit() {
+                continue;
+            }
+            if let Some(debit_ix) = self.purchase(&transactions, credit_ix) {
+                let credit_uid = transactions[credit_ix].uid();
+                let refunded = transactions[credit_ix].amount;
+                let purchase = &mut transactions[debit_ix];
+                reasons.insert(
+                    credit_ix,
+                    format!(
+                        "partial refund of {} on {}, netted against {:?}",
+                        purchase.uid(),
+                        purchase.date,
+                        purchase.category
+                    ),
+                );
+                let before = purchase.amount;
+                purchase.amount -= refunded;
+                purchase.refunded_by.push(credit_uid);
+                purchase.record(
+                    "PartialRefunds",
+                    rule.clone(),
+                    "amount",
+                    purchase.amount.to_string(),
+                );
+                // Refunded in full, so nothing is left to count, the same as
+                // when `RefundCollator` pairs them up.
+                if purchase.amount == Money::zero() {
+                    reasons.insert(
+                        debit_ix,
+                        format!("refunded in full by {}", purchase.refunded_by.join(", ")),
+                    );
+                } else if !purchase.splits.is_empty() {
+                    purchase.splits = purchase.scaled_splits(before);
+                    let splits = purchase
+                        .splits
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("; ");
+                    purchase.record("PartialRefunds", rule.clone(), "splits", splits);
                 }
             }
         }
 
-        let mut to_delete: Vec<_> = to_delete.into_iter().collect();
-        to_delete.sort();
-        to_delete.reverse();
+        let transactions = transactions
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, transaction)| match reasons.remove(&i) {
+                Some(reason) => {
+                    dropped.push(DroppedTransaction::new(
+                        transaction,
+                        "PartialRefunds",
+                        reason,
+                    ));
+                    None
+                }
+                None => Some(transaction),
+            })
+            .collect();
 
-        for i in to_delete {
-            transactions.remove(i);
+        Ok(transactions)
+    }
+}
+
+impl Collate for RefundCollator {
+    fn collate(
+        &self,
+        transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
+        let pairs = pair_up(
+            &transactions,
+            self.window,
+            |_| Money::zero(),
+            |debit, credit| debit.account_name == credit.account_name,
+        );
+
+        let mut reasons = HashMap::new();
+        for (debit_ix, credit_ix) in pairs {
+            let (debit, credit) = (&transactions[debit_ix], &transactions[credit_ix]);
+            reasons.insert(
+                debit_ix,
+                format!("refunded by {} on {}", credit.uid(), credit.date),
+            );
+            reasons.insert(
+                credit_ix,
+                format!("refund of {} on {}", debit.uid(), debit.date),
+            );
         }
 
+        let transactions = transactions
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, transaction)| match reasons.remove(&i) {
+                Some(reason) => {
+                    dropped.push(DroppedTransaction::new(transaction, "Refunds", reason));
+                    None
+                }
+                None => Some(transaction),
+            })
+            .collect();
+
         Ok(transactions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::loading::{
+            Split,
+            TransactionType::{self, *},
+        },
+        budgetronlib::fintime::Date,
+    };
+
+    fn t(
+        id: &str,
+        day: i32,
+        amount: i64,
+        transaction_type: TransactionType,
+        description: &str,
+    ) -> Transaction {
+        Transaction {
+            id: id.to_owned(),
+            date: Date::ymd(2019, 3, day),
+            description: description.to_owned(),
+            amount: Money::from_i64(amount),
+            transaction_type,
+            category: if transaction_type.is_debit() {
+                "Shopping"
+            } else {
+                "Income"
+            }
+            .to_owned(),
+            account_name: "Visa".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn partial_refunds() {
+        let transactions = vec![
+            t("order", 1, 120, Debit, "AMAZON MKTPLACE PMTS 1234"),
+            t("groceries", 2, 40, Debit, "SAFEWAY #0567"),
+            t("return", 10, 30, Credit, "AMAZON MKTPLACE REFUND"),
+            t("paycheck", 15, 500, Credit, "ACME PAYROLL"),
+            t("rebate", 20, 50, Credit, "SAFEWAY REBATE"),
+        ];
+        let mut dropped = Vec::new();
+        let transactions = PartialRefundCollator::new(30, 0.5)
+            .collate(transactions, &mut dropped)
+            .unwrap();
+
+        let summary = transactions
+            .iter()
+            .map(|t| (t.id.as_str(), t.amount, t.refunded_by.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("order", Money::from_i64(90), vec!["return".to_owned()]),
+                ("groceries", Money::from_i64(40), vec![]),
+                ("paycheck", Money::from_i64(500), vec![]),
+                ("rebate", Money::from_i64(50), vec![]),
+            ]
+        );
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].transaction.id, "return");
+        assert_eq!(
+            dropped[0].reason,
+            r#"partial refund of order on 3/1/2019, netted against "Shopping""#
+        );
+        assert_eq!(
+            transactions[0].provenance[0].rule,
+            "partial_refund_window = 30"
+        );
+    }
+
+    #[test]
+    fn split_purchase() {
+        let split = |amount: i64, category: &str| Split {
+            amount: Money::from_i64(amount),
+            category: category.to_owned(),
+            person: String::new(),
+            tags: vec![],
+        };
+        let mut order = t("order", 1, 100, Debit, "TARGET 0042");
+        order.splits = vec![split(60, "Groceries"), split(40, "Household")];
+        let mut dinner = t("dinner", 2, 100, Debit, "CAFE ROUGE");
+        dinner.splits = vec![split(30, "Dining")];
+        let transactions = vec![
+            order,
+            dinner,
+            t("return", 5, 25, Credit, "TARGET RETURN"),
+            t("comp", 6, 50, Credit, "CAFE ROUGE REFUND"),
+        ];
+        let transactions = PartialRefundCollator::new(30, 0.5)
+            .collate(transactions, &mut Vec::new())
+            .unwrap();
+
+        let parts = transactions[0].parts();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].amount, Money::from_i64(45));
+        assert_eq!(parts[1].amount, Money::from_i64(30));
+        let parts = transactions[1].parts();
+        assert_eq!(parts[0].amount, Money::from_i64(15));
+        assert_eq!(parts[1].amount, Money::from_i64(35));
+        assert!(transactions
+            .iter()
+            .flat_map(Transaction::parts)
+            .all(|p| !p.amount.is_negative()));
+    }
+
+    #[test]
+    fn full_refunds() {
+        let transactions = vec![
+            t("order", 1, 100, Debit, "AMAZON MKTPLACE PMTS 1234"),
+            t("return", 5, 40, Credit, "AMAZON MKTPLACE REFUND"),
+            t("rest", 9, 60, Credit, "AMAZON MKTPLACE REFUND"),
+        ];
+        let mut dropped = Vec::new();
+        let transactions = PartialRefundCollator::new(30, 0.5)
+            .collate(transactions, &mut dropped)
+            .unwrap();
+        assert!(transactions.is_empty());
+        assert_eq!(dropped.len(), 3);
+        assert_eq!(dropped[0].reason, "refunded in full by return, rest");
+    }
+}
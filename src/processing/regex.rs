@@ -9,7 +9,7 @@
 use {
     regex::{self, Captures},
     serde::de::{self, Deserialize, Deserializer, Visitor},
-    std::fmt,
+    std::{borrow::Cow, fmt},
 };
 
 #[derive(Debug)]
@@ -54,4 +54,12 @@ impl Regex {
     pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
         self.0.captures(text)
     }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn replace_all<'t>(&self, text: &'t str, replacement: &str) -> Cow<'t, str> {
+        self.0.replace_all(text, replacement)
+    }
 }
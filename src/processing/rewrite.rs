@@ -0,0 +1,126 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{loading::Transaction, processing::regex::Regex},
+    serde::Deserialize,
+};
+
+/// Cleans up a transaction's description. When `pattern` matches the current
+/// description, every match is replaced with `replace`, and `category`,
+/// `notes` and `labels` are set if given. All of them can refer to capture
+/// groups of the pattern as `$1` or `$name`.
+#[derive(Debug, Deserialize)]
+pub struct RewriteRule {
+    pattern: Regex,
+    replace: Option<String>,
+    category: Option<String>,
+    notes: Option<String>,
+    labels: Option<String>,
+}
+
+impl RewriteRule {
+    pub fn apply(&self, t: &mut Transaction) {
+        let (category, notes, labels) = match self.pattern.captures(&t.description) {
+            Some(captures) => {
+                let expand = |template: &Option<String>| {
+                    template.as_ref().map(|template| {
+                        let mut expanded = String::new();
+                        captures.expand(template, &mut expanded);
+                        expanded
+                    })
+                };
+                (
+                    expand(&self.category),
+                    expand(&self.notes),
+                    expand(&self.labels),
+                )
+            }
+            None => return,
+        };
+
+        let rule = format!("pattern = {:?}", self.pattern.as_str());
+        if let Some(ref replace) = self.replace {
+            t.description = self
+                .pattern
+                .replace_all(&t.description, replace.as_str())
+                .trim()
+                .to_owned();
+            let description = t.description.clone();
+            t.record("Rewrite", rule.clone(), "description", description);
+        }
+        if let Some(category) = category {
+            t.record("Rewrite", rule.clone(), "category", category.clone());
+            t.category = category;
+        }
+        if let Some(notes) = notes {
+            t.record("Rewrite", rule.clone(), "notes", notes.clone());
+            t.notes = notes;
+        }
+        if let Some(labels) = labels {
+            t.record("Rewrite", rule, "labels", labels.clone());
+            t.labels = labels;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(config: &str) -> Vec<RewriteRule> {
+        #[derive(Deserialize)]
+        struct Rules {
+            rewrite: Vec<RewriteRule>,
+        }
+        toml::from_str::<Rules>(config)
+            .expect("Unable to parse rules")
+            .rewrite
+    }
+
+    fn rewrite(rules: &[RewriteRule], description: &str) -> Transaction {
+        let mut t = Transaction {
+            description: description.to_owned(),
+            original_description: description.to_owned(),
+            ..Default::default()
+        };
+        for rule in rules {
+            rule.apply(&mut t);
+        }
+        t
+    }
+
+    #[test]
+    fn ordered_rules_with_captures() {
+        let rules = rules(
+            r#"
+            [[rewrite]]
+            pattern = '^SQ \*(?P<payee>.*?) \d+ .*$'
+            replace = "$payee"
+            labels = "square"
+
+            [[rewrite]]
+            pattern = '(?i)^blue bottle.*'
+            replace = "Blue Bottle Coffee"
+            category = "Coffee Shops"
+            notes = "was: $0"
+            "#,
+        );
+
+        let t = rewrite(&rules, "SQ *BLUE BOTTLE 0423 OAKLAND CA");
+        assert_eq!(t.description, "Blue Bottle Coffee");
+        assert_eq!(t.original_description, "SQ *BLUE BOTTLE 0423 OAKLAND CA");
+        assert_eq!(t.category, "Coffee Shops");
+        assert_eq!(t.notes, "was: BLUE BOTTLE");
+        assert_eq!(t.labels, "square");
+
+        let t = rewrite(&rules, "PAYROLL");
+        assert_eq!(t.description, "PAYROLL");
+        assert_eq!(t.category, "");
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{OwnerShare, Transaction},
+        processing::config::TransactionMatcher,
+    },
+    budgetronlib::error::{BResult, BudgetError},
+    serde::Deserialize,
+    std::collections::BTreeMap,
+};
+
+/// Shares the transactions `matcher` matches between owners, e.g.
+/// `shares = { Person1 = 70, Person2 = 30 }`. The shares are relative to each
+/// other, so `{ Person1 = 1, Person2 = 1 }` is half each.
+#[derive(Debug, Deserialize)]
+pub struct ShareRule {
+    shares: BTreeMap<String, f64>,
+    #[serde(flatten)]
+    matcher: TransactionMatcher,
+}
+
+impl ShareRule {
+    pub fn matches(&self, t: &Transaction) -> bool {
+        self.matcher.matches(t)
+    }
+
+    pub fn shares(&self) -> Vec<(String, f64)> {
+        self.shares
+            .iter()
+            .map(|(person, &share)| (person.clone(), share))
+            .collect()
+    }
+}
+
+/// Reads the shares written in a transaction's notes, on the line that starts
+/// with `prefix`. Owners are separated by `;` and each is a name and a share,
+/// e.g.
+///
+/// ```text
+/// shares: Person1 70%; Person2 30%
+/// ```
+pub fn parse_notes(notes: &str, prefix: &str) -> Option<Vec<(String, f64)>> {
+    let line = notes
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with(prefix))?;
+    let shares = line[prefix.len()..]
+        .split(';')
+        .filter_map(|part| {
+            let mut words = part.split_whitespace().collect::<Vec<_>>();
+            let share = words.pop()?.trim_end_matches('%').parse().ok()?;
+            if words.is_empty() {
+                None
+            } else {
+                Some((words.join(" "), share))
+            }
+        })
+        .collect::<Vec<_>>();
+    if shares.is_empty() {
+        None
+    } else {
+        Some(shares)
+    }
+}
+
+/// Turns relative shares into percentages of the transaction.
+pub fn owner_shares(shares: &[(String, f64)], t: &Transaction) -> BResult<Vec<OwnerShare>> {
+    let total = shares.iter().map(|&(_, share)| share).sum::<f64>();
+    if shares.iter().any(|&(_, share)| share < 0.0) || total <= 0.0 {
+        return Err(BudgetError::ParseError(format!(
+            "Unable to share {} ({} {}) between owners: the shares must be positive",
+            t.uid(),
+            t.original_description,
+            t.amount
+        )));
+    }
+    Ok(shares
+        .iter()
+        .map(|(person, share)| OwnerShare {
+            person: person.clone(),
+            percent: share / total * 100.0,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::loading::{Money, TransactionType},
+    };
+
+    #[test]
+    fn shares_add_up() {
+        let mut t = Transaction {
+            id: "rent".to_owned(),
+            amount: Money::parse_str("1000.01").unwrap(),
+            transaction_type: TransactionType::Debit,
+            person: "Joint".to_owned(),
+            ..Default::default()
+        };
+        let shares = parse_notes("June rent\nshares: Zach 2; Jane Doe 1", "shares:").unwrap();
+        assert_eq!(
+            shares,
+            vec![("Zach".to_owned(), 2.0), ("Jane Doe".to_owned(), 1.0)]
+        );
+        t.owner_shares = owner_shares(&shares, &t).unwrap();
+        let amounts = t.owner_amounts();
+        assert_eq!(amounts[0].0, "Zach");
+        assert_eq!(amounts[0].1.to_string(), "666.67");
+        assert_eq!(amounts[1].0, "Jane Doe");
+        assert_eq!(t.owner_shares[0].to_string(), "Zach 66.67%");
+        assert_eq!(amounts.iter().map(|&(_, a)| a).sum::<Money>(), t.amount);
+        assert!(t.is_owned_by("Jane Doe"));
+        assert!(!t.is_owned_by("Joint"));
+
+        assert_eq!(parse_notes("shares: 70%", "shares:"), None);
+        assert!(owner_shares(&[("Zach".to_owned(), 0.0)], &t).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Money, Split, Transaction},
+        processing::config::TransactionMatcher,
+    },
+    budgetronlib::error::{BResult, BudgetError},
+    serde::Deserialize,
+};
+
+/// Splits the transactions `matcher` matches into `parts`.
+#[derive(Debug, Deserialize)]
+pub struct SplitRule {
+    parts: Vec<SplitPart>,
+    #[serde(flatten)]
+    matcher: TransactionMatcher,
+}
+
+impl SplitRule {
+    pub fn matches(&self, t: &Transaction) -> bool {
+        self.matcher.matches(t)
+    }
+
+    pub fn parts(&self) -> &[SplitPart] {
+        &self.parts
+    }
+}
+
+/// One part of a split transaction. Its share is a fixed `amount`, a
+/// `percent` of the transaction, or with neither, whatever the other parts
+/// leave over. The category and person default to the transaction's own.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct SplitPart {
+    amount: Option<Money>,
+    percent: Option<f64>,
+    category: Option<String>,
+    person: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Reads the split written in a transaction's notes, on the line that starts
+/// with `prefix`. Parts are separated by `;` and each is an optional share
+/// (`12.50` or `30%`), a category, an optional `@person` and any `#tags`, e.g.
+///
+/// ```text
+/// split: 45.20 Groceries; 30% Household @Jane; Gift #birthday
+/// ```
+pub fn parse_notes(notes: &str, prefix: &str) -> Option<Vec<SplitPart>> {
+    let line = notes
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with(prefix))?;
+    let parts = line[prefix.len()..]
+        .split(';')
+        .map(|part| {
+            let mut split = SplitPart::default();
+            let mut words = part.split_whitespace().peekable();
+            if let Some(share) = words.peek() {
+                if share.ends_with('%') {
+                    split.percent = share.trim_end_matches('%').parse().ok();
+                } else {
+                    split.amount = Money::parse_str(share).ok();
+                }
+                if split.amount.is_some() || split.percent.is_some() {
+                    words.next();
+                }
+            }
+            let mut category = Vec::new();
+            for word in words {
+                if word.starts_with('@') {
+                    split.person = Some(word.trim_start_matches('@').to_owned());
+                } else if word.starts_with('#') {
+                    split.tags.push(word.trim_start_matches('#').to_owned());
+                } else {
+                    category.push(word);
+                }
+            }
+            if !category.is_empty() {
+                split.category = Some(category.join(" "));
+            }
+            split
+        })
+        .filter(|split| *split != SplitPart::default())
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts)
+    }
+}
+
+/// Works out how much of the transaction each part gets. Whatever is left
+/// over without a part to take the rest stays with the transaction's own
+/// category and owner, so the splits always add up to the transaction.
+pub fn split(parts: &[SplitPart], t: &Transaction) -> BResult<Vec<Split>> {
+    let error = |message: String| {
+        Err(BudgetError::ParseError(format!(
+            "Unable to split {} ({} {}): {}",
+            t.uid(),
+            t.original_description,
+            t.amount,
+            message
+        )))
+    };
+
+    let share = |part: &SplitPart| {
+        part.amount
+            .or_else(|| part.percent.map(|percent| t.amount * (percent / 100.0)))
+    };
+    let shared = parts.iter().filter_map(share).sum::<Money>();
+    let rest = t.amount - shared;
+    if rest.is_negative() {
+        return error(format!("the parts add up to {}", shared));
+    }
+    if parts.iter().filter(|p| share(p).is_none()).count() > 1 {
+        return error("more than one part takes the rest".to_owned());
+    }
+
+    let mut splits = parts
+        .iter()
+        .map(|part| Split {
+            amount: share(part).unwrap_or(rest),
+            category: part.category.clone().unwrap_or_else(|| t.category.clone()),
+            person: part.person.clone().unwrap_or_else(|| t.person.clone()),
+            tags: part.tags.clone(),
+        })
+        .collect::<Vec<_>>();
+    if parts.iter().all(|p| share(p).is_some()) && rest != Money::zero() {
+        splits.push(Split {
+            amount: rest,
+            category: t.category.clone(),
+            person: t.person.clone(),
+            tags: Vec::new(),
+        });
+    }
+    Ok(splits)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::loading::TransactionType};
+
+    fn costco() -> Transaction {
+        Transaction {
+            id: "costco".to_owned(),
+            original_description: "COSTCO WHSE #0042".to_owned(),
+            amount: Money::from_i64(200),
+            transaction_type: TransactionType::Debit,
+            category: "Shopping".to_owned(),
+            person: "Zach".to_owned(),
+            tags: vec!["costco".to_owned()],
+            ..Default::default()
+        }
+    }
+
+    fn summary(splits: &[Split]) -> Vec<String> {
+        splits.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn notes_syntax() {
+        let parts = parse_notes(
+            "Birthday run\nsplit: 45.20 Groceries; 25% Household Goods @Jane; Gift #birthday",
+            "split:",
+        )
+        .expect("No split found");
+        let splits = split(&parts, &costco()).unwrap();
+        assert_eq!(
+            summary(&splits),
+            vec![
+                "45.20 Groceries @Zach",
+                "50.00 Household Goods @Jane",
+                "104.80 Gift @Zach #birthday",
+            ]
+        );
+        assert_eq!(parse_notes("Just groceries", "split:"), None);
+    }
+
+    #[test]
+    fn parts_add_up() {
+        let parts = parse_notes("split: 50 Groceries; 30 Gift", "split:").unwrap();
+        let mut t = costco();
+        t.splits = split(&parts, &t).unwrap();
+        assert_eq!(
+            summary(&t.splits),
+            vec![
+                "50.00 Groceries @Zach",
+                "30.00 Gift @Zach",
+                "120.00 Shopping @Zach"
+            ]
+        );
+
+        // A refund of 20 that was not taken out of the splits comes out of
+        // each of them.
+        t.amount = Money::from_i64(180);
+        let parts = t.parts();
+        assert_eq!(
+            parts
+                .iter()
+                .map(|p| (p.category.as_str(), p.amount))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Groceries", Money::from_i64(45)),
+                ("Gift", Money::from_i64(27)),
+                ("Shopping", Money::from_i64(108)),
+            ]
+        );
+        assert_eq!(parts.iter().map(|p| p.amount).sum::<Money>(), t.amount);
+        assert!(parts.iter().all(|p| p.tags == vec!["costco".to_owned()]));
+
+        let too_much = parse_notes("split: 150 Groceries; 60 Gift", "split:").unwrap();
+        assert!(split(&too_much, &t).is_err());
+        let two_rests = parse_notes("split: Groceries; Gift", "split:").unwrap();
+        assert!(split(&two_rests, &t).is_err());
+    }
+}
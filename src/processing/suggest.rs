@@ -0,0 +1,212 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::loading::Transaction,
+    serde::Deserialize,
+    std::collections::{HashMap, HashSet},
+};
+
+/// The processor name that suggested categories are recorded under.
+pub const SUGGEST_CATEGORIES: &str = "SuggestCategories";
+
+/// When to fill in a category from what similar transactions were
+/// categorized as.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SuggestOptions {
+    /// How sure a suggestion must be, from 0 to 1, to be used.
+    #[serde(rename = "suggest_threshold")]
+    pub threshold: f64,
+    /// The categories that count as not categorized yet.
+    #[serde(default = "default_uncategorized")]
+    pub uncategorized: Vec<String>,
+}
+
+fn default_uncategorized() -> Vec<String> {
+    vec![String::new(), "Uncategorized".to_owned()]
+}
+
+impl Default for SuggestOptions {
+    fn default() -> SuggestOptions {
+        SuggestOptions {
+            threshold: 0.8,
+            uncategorized: default_uncategorized(),
+        }
+    }
+}
+
+impl SuggestOptions {
+    pub fn is_uncategorized(&self, t: &Transaction) -> bool {
+        self.uncategorized.contains(&t.category)
+    }
+}
+
+/// A category for a transaction, and how likely it is to be right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub category: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Default)]
+struct CategoryCounts {
+    transactions: usize,
+    features: HashMap<String, usize>,
+    total_features: usize,
+}
+
+/// A naive Bayes classifier over the words of a transaction's original
+/// description, its account, its type and roughly how large it is.
+#[derive(Debug, Default)]
+pub struct CategoryClassifier {
+    categories: HashMap<String, CategoryCounts>,
+    vocabulary: HashSet<String>,
+    transactions: usize,
+}
+
+/// What the classifier looks at in a transaction.
+fn features(t: &Transaction) -> Vec<String> {
+    let mut features = t
+        .original_description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .map(|word| format!("word:{}", word.to_uppercase()))
+        .collect::<Vec<_>>();
+    features.push(format!("account:{}", t.account_name));
+    features.push(format!("type:{:?}", t.transaction_type));
+    // Amounts in powers of two, so $3 coffees and $3000 rents look different
+    // but $40 and $45 groceries do not.
+    let amount = t.amount.abs().to_f64().max(1.0);
+    features.push(format!("amount:{}", amount.log2().floor()));
+    features
+}
+
+impl CategoryClassifier {
+    /// Learns from the transactions that are categorized, other than those
+    /// whose category was itself suggested.
+    pub fn train<'a, I>(transactions: I, options: &SuggestOptions) -> CategoryClassifier
+    where
+        I: IntoIterator<Item = &'a Transaction>,
+    {
+        let mut classifier = CategoryClassifier::default();
+        let training = transactions.into_iter().filter(|t| {
+            !options.is_uncategorized(t)
+                && !t
+                    .provenance
+                    .iter()
+                    .any(|c| c.processor == SUGGEST_CATEGORIES)
+        });
+        for t in training {
+            let counts = classifier
+                .categories
+                .entry(t.category.clone())
+                .or_insert_with(CategoryCounts::default);
+            counts.transactions += 1;
+            for feature in features(t) {
+                *counts.features.entry(feature.clone()).or_insert(0) += 1;
+                counts.total_features += 1;
+                classifier.vocabulary.insert(feature);
+            }
+            classifier.transactions += 1;
+        }
+        classifier
+    }
+
+    /// The most likely category for the transaction, unless the classifier
+    /// has not seen any categorized transactions.
+    pub fn suggest(&self, t: &Transaction) -> Option<Suggestion> {
+        let features = features(t)
+            .into_iter()
+            .filter(|f| self.vocabulary.contains(f))
+            .collect::<Vec<_>>();
+        let vocabulary = self.vocabulary.len() as f64;
+        let scores = self
+            .categories
+            .iter()
+            .map(|(category, counts)| {
+                let prior = (counts.transactions as f64 / self.transactions as f64).ln();
+                let likelihood = features
+                    .iter()
+                    .map(|f| {
+                        let count = counts.features.get(f).cloned().unwrap_or(0) as f64;
+                        ((count + 1.0) / (counts.total_features as f64 + vocabulary)).ln()
+                    })
+                    .sum::<f64>();
+                (category, prior + likelihood)
+            })
+            .collect::<Vec<_>>();
+
+        let (category, best) = scores
+            .iter()
+            .cloned()
+            .fold(None, |best, score| match best {
+                Some((_, most)) if most >= score.1 => best,
+                _ => Some(score),
+            })?;
+        let total = scores.iter().map(|(_, s)| (s - best).exp()).sum::<f64>();
+        Some(Suggestion {
+            category: category.clone(),
+            confidence: 1.0 / total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::loading::{Money, TransactionType},
+    };
+
+    fn t(description: &str, amount: i64, account: &str, category: &str) -> Transaction {
+        Transaction {
+            original_description: description.to_owned(),
+            amount: Money::from_i64(amount),
+            transaction_type: TransactionType::Debit,
+            account_name: account.to_owned(),
+            category: category.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn suggests_from_history() {
+        let history = vec![
+            t("SAFEWAY #1234", 82, "Visa", "Groceries"),
+            t("SAFEWAY #0042", 45, "Visa", "Groceries"),
+            t("TRADER JOE'S #552", 61, "Visa", "Groceries"),
+            t("BLUE BOTTLE COFFEE", 5, "Visa", "Coffee"),
+            t("PEET'S COFFEE #12", 4, "Visa", "Coffee"),
+            t("CITY OF OAKLAND RENT", 2400, "Checking", "Rent"),
+            t("MYSTERY CHARGE", 20, "Visa", "Uncategorized"),
+        ];
+        let classifier = CategoryClassifier::train(&history, &SuggestOptions::default());
+        assert_eq!(classifier.transactions, 6);
+
+        let safeway = classifier
+            .suggest(&t("SAFEWAY FUEL #1234", 70, "Visa", ""))
+            .unwrap();
+        assert_eq!(safeway.category, "Groceries");
+        assert!(safeway.confidence > 0.8, "{:?}", safeway);
+
+        let coffee = classifier
+            .suggest(&t("SIGHTGLASS COFFEE", 6, "Visa", ""))
+            .unwrap();
+        assert_eq!(coffee.category, "Coffee");
+
+        let unknown = classifier
+            .suggest(&t("ACME WIDGETS", 30, "Visa", ""))
+            .unwrap();
+        assert!(unknown.confidence < 0.8, "{:?}", unknown);
+
+        assert_eq!(
+            CategoryClassifier::train(&[], &SuggestOptions::default()).suggest(&history[0]),
+            None
+        );
+    }
+}
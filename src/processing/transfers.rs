@@ -8,99 +8,330 @@
 
 use {
     crate::{
-        loading::{Transaction, TransactionType},
-        processing::Collate,
+        loading::{Money, Transaction, TransactionType},
+        processing::{pairing::pair_up, Collate, DroppedTransaction, Window},
     },
     budgetronlib::error::BResult,
-    std::{
-        cmp::min,
-        collections::{HashMap, HashSet},
-        i64,
-    },
+    serde::Deserialize,
+    std::collections::HashMap,
 };
 
+/// Which debits and credits may be paired up as the two halves of a transfer,
+/// besides being in different accounts and within the collator's window.
+#[derive(Debug, Deserialize, Clone)]
+pub struct TransferOptions {
+    /// The most the two amounts may differ by.
+    #[serde(rename = "transfer_tolerance")]
+    tolerance: Option<Money>,
+    /// The most the two amounts may differ by, as a percentage of the amount
+    /// that was sent.
+    #[serde(rename = "transfer_tolerance_percent")]
+    tolerance_percent: Option<f64>,
+    /// The category of the transactions that record the difference.
+    #[serde(rename = "transfer_fee_category", default = "default_fee_category")]
+    fee_category: String,
+    /// Only pair up transfers between these accounts, in either direction.
+    #[serde(rename = "transfer_accounts")]
+    accounts: Option<Vec<(String, String)>>,
+}
+
+fn default_fee_category() -> String {
+    "Bank Fee".to_owned()
+}
+
+impl Default for TransferOptions {
+    fn default() -> TransferOptions {
+        TransferOptions {
+            tolerance: None,
+            tolerance_percent: None,
+            fee_category: default_fee_category(),
+            accounts: None,
+        }
+    }
+}
+
+impl TransferOptions {
+    fn tolerance(&self, sent: Money) -> Money {
+        let absolute = self.tolerance.unwrap_or_else(Money::zero);
+        let relative = self
+            .tolerance_percent
+            .map(|percent| sent.abs() * (percent / 100.0))
+            .unwrap_or_else(Money::zero);
+        if absolute > relative {
+            absolute
+        } else {
+            relative
+        }
+    }
+
+    fn allows_accounts(&self, from: &str, to: &str) -> bool {
+        self.accounts.iter().all(|accounts| {
+            accounts
+                .iter()
+                .any(|(a, b)| (a == from && b == to) || (a == to && b == from))
+        })
+    }
+
+    fn pairs(&self, debit: &Transaction, credit: &Transaction) -> bool {
+        debit.account_name != credit.account_name
+            && (debit.amount - credit.amount).abs() <= self.tolerance(debit.amount)
+            && self.allows_accounts(&debit.account_name, &credit.account_name)
+    }
+}
+
+/// Merges a debit and a credit of the same amount in different accounts
+/// into a single transfer. When the amounts differ within the configured
+/// tolerance, the transfer is booked at the smaller amount and the difference
+/// as a separate fee transaction.
 pub struct TransferCollator {
-    pub horizon: usize,
+    pub window: Window,
+    pub options: TransferOptions,
 }
 
 impl TransferCollator {
     pub fn new(horizon: usize) -> TransferCollator {
-        TransferCollator { horizon }
+        TransferCollator::with_options(Window::Transactions(horizon), TransferOptions::default())
+    }
+
+    pub fn with_options(window: Window, options: TransferOptions) -> TransferCollator {
+        TransferCollator { window, options }
+    }
+
+    /// Books the difference between the two halves of a transfer. Money lost
+    /// on the way is a debit from the sending account, and money gained a
+    /// credit to the receiving one.
+    fn difference(&self, debit: &Transaction, credit: &Transaction, rule: &str) -> Transaction {
+        let (account, transaction_type, description) = if debit.amount > credit.amount {
+            (
+                &debit.account_name,
+                TransactionType::Debit,
+                format!("Fee for transfer to {}", credit.account_name),
+            )
+        } else {
+            (
+                &credit.account_name,
+                TransactionType::Credit,
+                format!("Difference for transfer from {}", debit.account_name),
+            )
+        };
+        let mut fee = Transaction {
+            id: format!("{}-fee", debit.uid()),
+            date: debit.date,
+            description: description.clone(),
+            original_description: description,
+            amount: (debit.amount - credit.amount).abs(),
+            transaction_type,
+            person: debit.person.clone(),
+            category: self.options.fee_category.clone(),
+            original_category: self.options.fee_category.clone(),
+            account_name: account.clone(),
+            currency: debit.currency,
+            ..Default::default()
+        };
+        fee.record(
+            "Transfers",
+            rule.to_owned(),
+            "category",
+            self.options.fee_category.clone(),
+        );
+        fee
     }
 }
 
 impl Collate for TransferCollator {
-    fn collate(&self, mut transactions: Vec<Transaction>) -> BResult<Vec<Transaction>> {
-        let mut to_delete = HashSet::new();
-        let mut to_update = HashMap::new();
-        for (i, t) in transactions.iter().enumerate() {
-            loop {
-                let candidates: Vec<_> = (i..min(transactions.len(), i + self.horizon))
-                    .filter(|&j| {
-                        let tn = &transactions[j];
-                        tn.amount == t.amount
-                            && !to_delete.contains(&i)
-                            && !to_delete.contains(&j)
-                            && !to_update.contains_key(&i)
-                            && !to_update.contains_key(&j)
-                    })
-                    .collect();
-
-                if candidates.len() <= 1 {
-                    break;
-                }
+    fn collate(
+        &self,
+        mut transactions: Vec<Transaction>,
+        dropped: &mut Vec<DroppedTransaction>,
+    ) -> BResult<Vec<Transaction>> {
+        let pairs = pair_up(
+            &transactions,
+            self.window,
+            |amount| self.options.tolerance(amount),
+            |debit, credit| self.options.pairs(debit, credit),
+        );
 
-                let mut mindelta = i64::MAX;
-                let mut found_transfer = (0, 0);
-                let debits = candidates
-                    .iter()
-                    .filter(|&i| transactions[*i].transaction_type.is_debit());
-
-                for debit_ix in debits {
-                    let debit = &transactions[*debit_ix];
-                    let credits = candidates
-                        .iter()
-                        .filter(|&i| transactions[*i].transaction_type.is_credit());
-                    for credit_ix in credits {
-                        let credit = &transactions[*credit_ix];
-                        if (debit.date - credit.date).abs() < mindelta
-                            && debit.account_name != credit.account_name
-                        {
-                            found_transfer = (*debit_ix, *credit_ix);
-                            mindelta = (debit.date - credit.date).abs();
-                        }
-                    }
+        let rule = self.window.rule("transfer");
+        let mut reasons = HashMap::new();
+        let mut fees = Vec::new();
+        for (i, credit_ix) in pairs {
+            if transactions[i].amount != transactions[credit_ix].amount {
+                fees.push(self.difference(&transactions[i], &transactions[credit_ix], &rule));
+            }
+            let destination_account = transactions[credit_ix].account_name.clone();
+            let amount = if transactions[i].amount < transactions[credit_ix].amount {
+                transactions[i].amount
+            } else {
+                transactions[credit_ix].amount
+            };
+            let transaction = &mut transactions[i];
+            transaction.transfer_destination_account = Some(destination_account.clone());
+            transaction.transaction_type = TransactionType::Transfer;
+            transaction.record(
+                "Transfers",
+                rule.clone(),
+                "transaction_type",
+                "Transfer".to_owned(),
+            );
+            transaction.record(
+                "Transfers",
+                rule.clone(),
+                "transfer_destination_account",
+                destination_account,
+            );
+            if transaction.amount != amount {
+                transaction.amount = amount;
+                transaction.record("Transfers", rule.clone(), "amount", amount.to_string());
+            }
+            reasons.insert(
+                credit_ix,
+                format!(
+                    "merged into transfer {} from {}",
+                    transaction.uid(),
+                    transaction.account_name
+                ),
+            );
+        }
+
+        let mut transactions = transactions
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, transaction)| match reasons.remove(&i) {
+                Some(reason) => {
+                    dropped.push(DroppedTransaction::new(transaction, "Transfers", reason));
+                    None
                 }
+                None => Some(transaction),
+            })
+            .collect::<Vec<_>>();
 
-                if found_transfer != (0, 0) {
-                    let tn = &transactions[found_transfer.1];
+        if !fees.is_empty() {
+            transactions.extend(fees);
+            transactions.sort_by_key(|t| t.date);
+        }
+        Ok(transactions)
+    }
+}
 
-                    to_delete.insert(found_transfer.1);
-                    to_update.insert(found_transfer.0, tn.account_name.clone());
+#[cfg(test)]
+mod tests {
+    use {super::*, budgetronlib::fintime::Date};
 
-                    if found_transfer.0 == i || found_transfer.1 == i {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
+    fn t(day: i32, amount: i64, transaction_type: TransactionType, account: &str) -> Transaction {
+        Transaction {
+            id: format!("{}-{}", account, day),
+            date: Date::ymd(2019, 1, day),
+            amount: Money::from_i64(amount),
+            transaction_type,
+            account_name: account.to_owned(),
+            ..Default::default()
         }
+    }
 
-        for (i, destination_account) in to_update {
-            if let Some(transaction) = transactions.get_mut(i) {
-                transaction.transfer_destination_account = Some(destination_account);
-                transaction.transaction_type = TransactionType::Transfer;
-            }
-        }
+    fn options(config: &str) -> TransferOptions {
+        toml::from_str(config).expect("Unable to parse options")
+    }
 
-        let mut to_delete: Vec<_> = to_delete.into_iter().collect();
-        to_delete.sort();
-        to_delete.reverse();
+    #[test]
+    fn date_window() {
+        use self::TransactionType::*;
+        let transactions = vec![
+            t(1, 100, Debit, "Checking"),
+            t(2, 5, Debit, "Checking"),
+            t(3, 5, Debit, "Checking"),
+            t(4, 100, Credit, "Savings"),
+            t(20, 50, Debit, "Checking"),
+            t(30, 50, Credit, "Savings"),
+        ];
 
-        for i in to_delete {
-            transactions.remove(i);
-        }
-        Ok(transactions)
+        // The 100 transfer is three transactions but only three days apart,
+        // and the 50 one is the other way around.
+        let by_count = TransferCollator::new(2)
+            .collate(transactions.clone(), &mut Vec::new())
+            .unwrap();
+        let transfers = by_count
+            .iter()
+            .filter(|t| t.transaction_type.is_transfer())
+            .map(|t| t.amount)
+            .collect::<Vec<_>>();
+        assert_eq!(transfers, vec![Money::from_i64(50)]);
+
+        let mut dropped = Vec::new();
+        let by_date = TransferCollator::with_options(Window::Days(5), TransferOptions::default())
+            .collate(transactions, &mut dropped)
+            .unwrap();
+        let transfers = by_date
+            .iter()
+            .filter(|t| t.transaction_type.is_transfer())
+            .map(|t| t.amount)
+            .collect::<Vec<_>>();
+        assert_eq!(transfers, vec![Money::from_i64(100)]);
+        assert_eq!(
+            by_date[0].transfer_destination_account,
+            Some("Savings".to_owned())
+        );
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].transaction.id, "Savings-4");
+    }
+
+    #[test]
+    fn fees_and_accounts() {
+        use self::TransactionType::*;
+        let transactions = vec![
+            t(1, 1015, Debit, "Checking"),
+            t(2, 1000, Credit, "Brokerage"),
+            t(3, 200, Debit, "Checking"),
+            t(3, 200, Credit, "Joint"),
+        ];
+        let collator = TransferCollator::with_options(
+            Window::Days(5),
+            options(
+                r#"
+                transfer_tolerance = 20.0
+                transfer_accounts = [["Brokerage", "Checking"]]
+                "#,
+            ),
+        );
+
+        let transactions = collator.collate(transactions, &mut Vec::new()).unwrap();
+        let summary = transactions
+            .iter()
+            .map(|t| {
+                (
+                    t.account_name.as_str(),
+                    t.amount,
+                    t.transaction_type,
+                    t.category.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("Checking", Money::from_i64(1000), Transfer, ""),
+                ("Checking", Money::from_i64(15), Debit, "Bank Fee"),
+                ("Checking", Money::from_i64(200), Debit, ""),
+                ("Joint", Money::from_i64(200), Credit, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn currencies_must_match() {
+        use self::TransactionType::*;
+        let mut sent = t(1, 100, Debit, "Girokonto");
+        sent.currency = Some("EUR".parse().unwrap());
+        let mut received = t(2, 100, Credit, "Checking");
+        received.currency = Some("USD".parse().unwrap());
+
+        let mut dropped = Vec::new();
+        let transactions = TransferCollator::new(10)
+            .collate(vec![sent, received], &mut dropped)
+            .unwrap();
+        assert!(dropped.is_empty());
+        assert!(transactions
+            .iter()
+            .all(|t| !t.transaction_type.is_transfer()));
+        assert_eq!(transactions.len(), 2);
     }
 }
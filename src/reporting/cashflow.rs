@@ -8,7 +8,7 @@
 
 use {
     crate::{
-        loading::{Money, Transaction, TransactionType},
+        loading::{Currency, Money, Transaction, TransactionType},
         reporting::{config::ReportOptions, timeseries::Timeseries, Reporter},
     },
     budgetronlib::fintime::Date,
@@ -26,6 +26,8 @@ pub struct CashflowReport {
     credit: Money,
     debit: Money,
     net: Money,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<Currency>,
     timeseries: Option<Timeseries<CashflowDatum>>,
 }
 
@@ -67,6 +69,7 @@ impl Reporter for Cashflow {
         };
 
         let cashflow: CashflowReport = transactions.fold(report, |mut report, ref t| {
+            report.currency = report.currency.or(t.currency);
             match t.transaction_type {
                 TransactionType::Credit => {
                     report.credit += t.amount;
@@ -95,12 +98,19 @@ impl Reporter for Cashflow {
 
 impl fmt::Display for CashflowReport {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = match self.currency {
+            Some(currency) => format!(" {}", currency),
+            None => String::new(),
+        };
         writeln!(
             f,
-            "In: ${:0.2}  Out: ${:0.2}  Delta: ${:0.2}",
+            "In: {:0.2}{}  Out: {:0.2}{}  Delta: {:0.2}{}",
             self.credit,
+            code,
             self.debit,
-            self.credit - self.debit
+            code,
+            self.credit - self.debit,
+            code
         )
     }
 }
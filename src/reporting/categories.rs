@@ -72,7 +72,11 @@ impl Reporter for Categories {
                     TransactionType::Debit => -transaction.amount,
                     _ => Money::zero(),
                 };
-                entry.transactions.push(transaction.uid());
+                let uid = transaction.uid();
+                // The parts of a split transaction can share a category.
+                if entry.transactions.last() != Some(&uid) {
+                    entry.transactions.push(uid);
+                }
             }
             let ts_data = report.ts_data();
             if let Some(ref mut ts) = report.timeseries {
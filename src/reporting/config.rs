@@ -9,7 +9,10 @@
 use {
     crate::{
         loading::{Money, Transaction, TransactionType},
-        reporting::{rolling_budget, Cashflow, Categories, IncomeExpenseRatio, Reporter},
+        reporting::{
+            rolling_budget, split_up, Cashflow, Categories, DuplicateCharges, IncomeExpenseRatio,
+            OwnerSpending, RepeatedTransactions, Reporter, Settlement,
+        },
     },
     budgetronlib::fintime::Date,
     serde::{Deserialize, Serialize},
@@ -80,6 +83,13 @@ pub enum ReportType {
         #[serde(default)]
         options: ReportOptions,
     },
+    RepeatedTransactions(RepeatedTransactions),
+    DuplicateCharges(DuplicateCharges),
+    Settlement(Settlement),
+    OwnerSpending {
+        #[serde(default)]
+        options: ReportOptions,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
@@ -232,7 +242,60 @@ impl Report {
         I: Iterator<Item = Cow<'a, Transaction>> + Clone,
         R: Reporter,
     {
-        self.filter_report_skip_tags(reporter, transactions, end_date)
+        self.filter_report_skip_tags(reporter, split_up(transactions), end_date)
+    }
+}
+
+/// Money in and out of the transactions a report includes.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ReportTotals {
+    pub credit: Money,
+    pub debit: Money,
+    pub net: Money,
+}
+
+impl Report {
+    /// A cashflow report with the same filters as this one, over its whole
+    /// date range.
+    fn totals_report(&self) -> Report {
+        Report {
+            name: self.name.clone(),
+            only_type: self.only_type,
+            skip_tags: self.skip_tags.clone(),
+            only_tags: self.only_tags.clone(),
+            only_owners: self.only_owners.clone(),
+            config: ReportType::Cashflow {
+                options: ReportOptions::default(),
+            },
+            ui_config: UIConfig::default(),
+            by_week: false,
+            by_month: false,
+            by_quarter: false,
+            by_year: false,
+        }
+    }
+}
+
+impl ConfiguredReports {
+    /// The totals of the transactions each report includes, by report name.
+    pub fn totals<'a, I>(&self, transactions: I, end_date: Date) -> Vec<(String, ReportTotals)>
+    where
+        I: Iterator<Item = Cow<'a, Transaction>> + Clone,
+    {
+        self.report
+            .iter()
+            .map(|report_config| {
+                let value = report_config.totals_report().run_report(
+                    &Cashflow::with_options(ReportOptions::default()),
+                    transactions.clone(),
+                    end_date,
+                );
+                (
+                    report_config.name.clone(),
+                    serde_json::from_value(value).expect("Could not read cashflow report"),
+                )
+            })
+            .collect()
     }
 }
 
@@ -272,6 +335,20 @@ impl Reporter for ConfiguredReports {
                     transactions.clone(),
                     end_date,
                 ),
+                ReportType::RepeatedTransactions(ref repeats) => {
+                    report_config.run_report(repeats, transactions.clone(), end_date)
+                }
+                ReportType::DuplicateCharges(ref duplicates) => {
+                    report_config.run_report(duplicates, transactions.clone(), end_date)
+                }
+                ReportType::Settlement(ref settlement) => {
+                    report_config.run_report(settlement, transactions.clone(), end_date)
+                }
+                ReportType::OwnerSpending { ref options } => report_config.run_report(
+                    &OwnerSpending::with_options((*options).clone()),
+                    transactions.clone(),
+                    end_date,
+                ),
             };
 
             let mut report_data = serde_json::map::Map::new();
@@ -290,3 +367,112 @@ impl Reporter for ConfiguredReports {
         Some("reports".to_owned())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::loading::{Money, OwnerShare, Split},
+    };
+
+    fn costco() -> Transaction {
+        let split = |amount, category: &str, person: &str| Split {
+            amount: Money::from_i64(amount),
+            category: category.to_owned(),
+            person: person.to_owned(),
+            tags: vec![],
+        };
+        Transaction {
+            id: "costco".to_owned(),
+            date: Date::ymd(2019, 4, 6),
+            amount: Money::from_i64(200),
+            transaction_type: TransactionType::Debit,
+            category: "Shopping".to_owned(),
+            person: "Zach".to_owned(),
+            splits: vec![
+                split(120, "Groceries", "Both"),
+                split(50, "Household", "Jane"),
+                split(30, "Gift", "Zach"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reports_count_split_parts() {
+        let reports: ConfiguredReports = toml::from_str(
+            r#"
+            [[report]]
+            name = "Categories"
+            config = { type = "Categories" }
+
+            [[report]]
+            name = "Jane"
+            only_owners = ["Jane"]
+            config = { type = "Cashflow" }
+
+            [[report]]
+            name = "Budget"
+            config = { type = "RollingBudget", split = "Both", amounts = { "04/01/2019" = { Zach = 300, Jane = 100 } } }
+            "#,
+        )
+        .expect("Unable to parse reports");
+        let transactions = [costco()];
+        let end_date = Date::ymd(2019, 4, 30);
+
+        let totals = reports.totals(transactions.iter().map(Cow::Borrowed), end_date);
+        assert_eq!(totals[1].1.debit, Money::from_i64(50));
+
+        let report = reports.report(transactions.iter().map(Cow::Borrowed), end_date);
+        let categories = &report[0]["data"]["categories"];
+        assert_eq!(categories["Groceries"]["amount"], "-120.00");
+        assert_eq!(categories["Gift"]["amount"], "-30.00");
+        assert!(categories.get("Shopping").is_none());
+
+        let budget = &report[2]["data"];
+        assert_eq!(budget["budgets"]["Zach"], "180.00");
+        assert_eq!(budget["budgets"]["Jane"], "20.00");
+        assert_eq!(budget["transactions"], serde_json::json!(["costco"]));
+    }
+
+    #[test]
+    fn reports_honor_owner_shares() {
+        let reports: ConfiguredReports = toml::from_str(
+            r#"
+            [[report]]
+            name = "Jane"
+            only_owners = ["Jane"]
+            config = { type = "Cashflow" }
+
+            [[report]]
+            name = "Budget"
+            config = { type = "RollingBudget", split = "Both", amounts = { "04/01/2019" = { Zach = 300, Jane = 100 } } }
+            "#,
+        )
+        .expect("Unable to parse reports");
+        let share = |person: &str, percent| OwnerShare {
+            person: person.to_owned(),
+            percent,
+        };
+        let rent = Transaction {
+            id: "rent".to_owned(),
+            date: Date::ymd(2019, 4, 1),
+            amount: Money::from_i64(1000),
+            transaction_type: TransactionType::Debit,
+            person: "Both".to_owned(),
+            owner_shares: vec![share("Zach", 70.0), share("Jane", 30.0)],
+            ..Default::default()
+        };
+        let transactions = [rent];
+        let end_date = Date::ymd(2019, 4, 30);
+
+        let totals = reports.totals(transactions.iter().map(Cow::Borrowed), end_date);
+        assert_eq!(totals[0].1.debit, Money::from_i64(300));
+
+        // Not in proportion to the budgets, which would be 750 and 250.
+        let report = reports.report(transactions.iter().map(Cow::Borrowed), end_date);
+        let budget = &report[1]["data"];
+        assert_eq!(budget["budgets"]["Zach"], "-400.00");
+        assert_eq!(budget["budgets"]["Jane"], "-200.00");
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Money, Transaction},
+        processing::default_duplicate_tag,
+        reporting::Reporter,
+    },
+    budgetronlib::fintime::Date,
+    serde::{Deserialize, Serialize},
+    serde_json::{self, Value},
+    std::{borrow::Cow, collections::BTreeMap},
+};
+
+/// Lists the charges the "DuplicateCharges" processor flagged, grouped by
+/// merchant, account and amount.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DuplicateCharges {
+    /// The tag the processor adds.
+    #[serde(default = "default_duplicate_tag")]
+    tag: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateCharge {
+    merchant: String,
+    account: String,
+    amount: Money,
+    dates: Vec<Date>,
+    /// What was charged on top of the first charge.
+    extra: Money,
+    transactions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicatesReport {
+    duplicates: Vec<DuplicateCharge>,
+    total_extra: Money,
+}
+
+impl Reporter for DuplicateCharges {
+    fn report<'a, I>(&self, transactions: I, _: Date) -> Value
+    where
+        I: Iterator<Item = Cow<'a, Transaction>>,
+    {
+        // The parts of a split transaction come one after another, so they
+        // are put back together to be counted once.
+        let mut flagged: Vec<Cow<Transaction>> = Vec::new();
+        for t in transactions.filter(|t| t.tags.contains(&self.tag)) {
+            match flagged.last_mut() {
+                Some(last) if last.uid() == t.uid() => last.to_mut().amount += t.amount,
+                _ => flagged.push(t),
+            }
+        }
+
+        let mut groups = BTreeMap::new();
+        for t in &flagged {
+            groups
+                .entry((t.merchant(), t.account_name.clone(), t.amount))
+                .or_insert_with(Vec::new)
+                .push(t);
+        }
+
+        let duplicates = groups
+            .into_iter()
+            .map(|((merchant, account, amount), charges)| DuplicateCharge {
+                merchant,
+                account,
+                amount,
+                dates: charges.iter().map(|t| t.date).collect(),
+                extra: amount * (charges.len() as i32 - 1),
+                transactions: charges.iter().map(|t| t.uid()).collect(),
+            })
+            .collect::<Vec<_>>();
+        let total_extra = duplicates.iter().map(|d| d.extra).sum();
+
+        serde_json::to_value(DuplicatesReport {
+            duplicates,
+            total_extra,
+        })
+        .expect("Couldn't serialize duplicate charges")
+    }
+
+    fn key(&self) -> Option<String> {
+        Some("duplicate_charges".to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::loading::TransactionType};
+
+    #[test]
+    fn lists_flagged_charges() {
+        let charge = |day, amount: i64, tagged: bool| Transaction {
+            id: format!("hotel-{}", day),
+            date: Date::ymd(2019, 3, day),
+            description: format!("Hotel California #{}", day),
+            amount: Money::from_i64(amount),
+            transaction_type: TransactionType::Debit,
+            account_name: "Visa".to_owned(),
+            tags: if tagged {
+                vec!["possible-duplicate".to_owned()]
+            } else {
+                vec![]
+            },
+            ..Default::default()
+        };
+        let transactions = [
+            charge(1, 240, true),
+            charge(2, 240, true),
+            charge(9, 240, false),
+        ];
+
+        let reporter: DuplicateCharges = toml::from_str("").unwrap();
+        let report = reporter.report(
+            transactions.iter().map(Cow::Borrowed),
+            Date::ymd(2019, 3, 31),
+        );
+        assert_eq!(report["total_extra"], "240.00");
+        assert_eq!(report["duplicates"][0]["transactions"][1], "hotel-2");
+        assert_eq!(report["duplicates"].as_array().unwrap().len(), 1);
+        assert_eq!(report["duplicates"][0]["merchant"], "HOTEL CALIFORNIA");
+    }
+}
@@ -1,12 +1,30 @@
 use {
-    crate::{loading::Transaction, reporting::Reporter},
+    crate::{loading::Transaction, processing::DroppedTransaction, reporting::Reporter},
     budgetronlib::fintime::{Date, Timeframe},
+    serde::Serialize,
     serde_json::{self, Value},
     std::{borrow::Cow, collections::HashMap},
 };
 
-pub struct List;
-impl Reporter for List {
+/// Lists the transactions of the last three years by id, along with the
+/// transactions processors dropped during that time and why.
+pub struct List<'d> {
+    dropped: &'d [DroppedTransaction],
+}
+
+impl<'d> List<'d> {
+    pub fn new(dropped: &'d [DroppedTransaction]) -> List<'d> {
+        List { dropped }
+    }
+}
+
+#[derive(Serialize)]
+struct ListReport<'a, 'd> {
+    transactions: HashMap<String, Cow<'a, Transaction>>,
+    dropped: HashMap<String, &'d DroppedTransaction>,
+}
+
+impl<'d> Reporter for List<'d> {
     fn report<'a, I>(&self, transactions: I, _: Date) -> Value
     where
         I: Iterator<Item = Cow<'a, Transaction>>,
@@ -24,7 +42,17 @@ impl Reporter for List {
                 }
             })
             .collect::<HashMap<_, _>>();
-        serde_json::to_value(&transaction_map).expect("Couldn't serialize")
+        let dropped_map = self
+            .dropped
+            .iter()
+            .filter(|d| d.transaction.date >= start_date)
+            .map(|d| (d.transaction.uid(), d))
+            .collect::<HashMap<_, _>>();
+        serde_json::to_value(&ListReport {
+            transactions: transaction_map,
+            dropped: dropped_map,
+        })
+        .expect("Couldn't serialize")
     }
 
     fn key(&self) -> Option<String> {
@@ -67,6 +67,22 @@ pub trait Reporter: Sized {
 
 pub trait Report: fmt::Display + serde::Serialize {}
 
+/// Replaces split transactions with their parts, so that reports and report
+/// filters count each part with its own category, owner and tags. The parts
+/// of a transaction share its id and come one after another.
+pub fn split_up<'a, I>(transactions: I) -> impl Iterator<Item = Cow<'a, Transaction>> + Clone
+where
+    I: Iterator<Item = Cow<'a, Transaction>> + Clone,
+{
+    transactions.flat_map(|t| {
+        if t.splits.is_empty() {
+            vec![t]
+        } else {
+            t.parts().into_iter().map(Cow::Owned).collect()
+        }
+    })
+}
+
 mod by_account;
 mod by_timeframe;
 mod cashflow;
@@ -74,6 +90,7 @@ mod categories;
 mod config;
 #[cfg(feature = "db")]
 mod database;
+mod duplicates;
 mod excluding_tags;
 mod income_expense_ratio;
 mod list;
@@ -82,15 +99,30 @@ mod net_worth;
 mod only_owners;
 mod only_tags;
 mod only_type;
+mod owner_spending;
+mod repeats;
 mod rolling_budget;
+mod settlement;
 mod timeseries;
 
 #[cfg(feature = "db")]
 pub use crate::reporting::database::Database;
 pub use crate::reporting::{
-    by_account::ByAccountReport, by_timeframe::ByTimeframeReport, cashflow::Cashflow,
-    categories::Categories, config::ConfiguredReports, excluding_tags::ExcludingTags,
-    income_expense_ratio::IncomeExpenseRatio, list::List, net_worth::NetWorth,
-    only_owners::OnlyOwners, only_tags::OnlyTags, only_type::OnlyType,
+    by_account::ByAccountReport,
+    by_timeframe::ByTimeframeReport,
+    cashflow::Cashflow,
+    categories::Categories,
+    config::{ConfiguredReports, ReportTotals},
+    duplicates::DuplicateCharges,
+    excluding_tags::ExcludingTags,
+    income_expense_ratio::IncomeExpenseRatio,
+    list::List,
+    net_worth::NetWorth,
+    only_owners::OnlyOwners,
+    only_tags::OnlyTags,
+    only_type::OnlyType,
+    owner_spending::OwnerSpending,
+    repeats::RepeatedTransactions,
     rolling_budget::RollingBudget,
+    settlement::Settlement,
 };
@@ -38,9 +38,27 @@ where
     where
         I: Iterator<Item = Cow<'b, Transaction>>,
     {
-        let (transactions, _): (Vec<_>, Vec<_>) =
-            transactions.partition(|t| self.owners.iter().any(|owner| t.person == *owner));
-        self.inner.report(transactions.into_iter(), end_date)
+        // A shared transaction counts as a copy with each owner's share.
+        let mut owned = Vec::new();
+        for t in transactions {
+            if t.owner_shares.is_empty() {
+                if self.owners.iter().any(|owner| t.is_owned_by(owner)) {
+                    owned.push(t);
+                }
+                continue;
+            }
+            for (person, amount) in t.owner_amounts() {
+                if self.owners.iter().any(|owner| person == owner) {
+                    owned.push(Cow::Owned(Transaction {
+                        amount,
+                        person: person.to_owned(),
+                        owner_shares: Vec::new(),
+                        ..t.as_ref().clone()
+                    }));
+                }
+            }
+        }
+        self.inner.report(owned.into_iter(), end_date)
     }
 
     fn key(&self) -> Option<String> {
@@ -0,0 +1,144 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Money, Transaction, TransactionType},
+        reporting::{config::ReportOptions, timeseries::Timeseries, Reporter},
+    },
+    budgetronlib::fintime::Date,
+    serde::Serialize,
+    serde_json::{self, Value},
+    std::{borrow::Cow, collections::BTreeMap},
+};
+
+/// How much each owner spent and received, counting their share of shared
+/// transactions.
+pub struct OwnerSpending {
+    options: ReportOptions,
+}
+
+impl OwnerSpending {
+    pub fn with_options(options: ReportOptions) -> OwnerSpending {
+        OwnerSpending { options }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OwnerEntry {
+    spent: Money,
+    received: Money,
+    /// What was spent in each category.
+    categories: BTreeMap<String, Money>,
+    transactions: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct OwnerSpendingReport {
+    owners: BTreeMap<String, OwnerEntry>,
+    timeseries: Option<Timeseries<BTreeMap<String, Money>>>,
+}
+
+impl OwnerSpendingReport {
+    fn ts_data(&self) -> BTreeMap<String, Money> {
+        self.owners
+            .iter()
+            .map(|(name, entry)| (name.to_owned(), entry.spent))
+            .collect()
+    }
+}
+
+impl Reporter for OwnerSpending {
+    fn report<'a, I>(&self, transactions: I, _: Date) -> Value
+    where
+        I: Iterator<Item = Cow<'a, Transaction>>,
+    {
+        let mut report = OwnerSpendingReport {
+            timeseries: if self.options.include_graph {
+                Some(Timeseries::new())
+            } else {
+                None
+            },
+            ..Default::default()
+        };
+
+        for t in transactions {
+            if t.transaction_type == TransactionType::Transfer {
+                continue;
+            }
+            let uid = t.uid();
+            for (person, amount) in t.owner_amounts() {
+                let entry = report.owners.entry(person.to_owned()).or_default();
+                if t.transaction_type.is_debit() {
+                    entry.spent += amount;
+                    *entry
+                        .categories
+                        .entry(t.category.clone())
+                        .or_insert_with(Money::zero) += amount;
+                } else {
+                    entry.received += amount;
+                }
+                if entry.transactions.last() != Some(&uid) {
+                    entry.transactions.push(uid.clone());
+                }
+            }
+            let data = report.ts_data();
+            if let Some(ref mut ts) = report.timeseries {
+                ts.add(t.date, data);
+            }
+        }
+
+        serde_json::to_value(&report).expect("Couldn't serialize owner spending")
+    }
+
+    fn key(&self) -> Option<String> {
+        Some("owner_spending".to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::loading::OwnerShare};
+
+    #[test]
+    fn counts_shares() {
+        let purchase = |amount: i64, category: &str, person: &str| Transaction {
+            id: format!("{}-{}", category, amount),
+            amount: Money::from_i64(amount),
+            transaction_type: TransactionType::Debit,
+            category: category.to_owned(),
+            person: person.to_owned(),
+            ..Default::default()
+        };
+        let mut rent = purchase(2000, "Rent", "Joint");
+        rent.owner_shares = vec![
+            OwnerShare {
+                person: "Zach".to_owned(),
+                percent: 70.0,
+            },
+            OwnerShare {
+                person: "Jane".to_owned(),
+                percent: 30.0,
+            },
+        ];
+        let transactions = [
+            rent,
+            purchase(50, "Groceries", "Jane"),
+            purchase(20, "Coffee", "Zach"),
+        ];
+
+        let report = OwnerSpending::with_options(ReportOptions::default()).report(
+            transactions.iter().map(Cow::Borrowed),
+            Date::ymd(2019, 1, 31),
+        );
+        assert_eq!(report["owners"]["Zach"]["spent"], "1420.00");
+        assert_eq!(report["owners"]["Jane"]["spent"], "650.00");
+        assert_eq!(report["owners"]["Jane"]["categories"]["Rent"], "600.00");
+        assert!(report["owners"].get("Joint").is_none());
+    }
+}
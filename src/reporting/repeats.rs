@@ -7,142 +7,379 @@
 // except according to those terms.
 
 use {
-    loading::Transaction,
-    num_traits::Float,
-    ordered_float::NotNaN,
-    reporting::Reporter,
+    crate::{
+        loading::{Money, Transaction},
+        reporting::Reporter,
+    },
+    budgetronlib::fintime::{Date, Timeframe},
+    serde::{Deserialize, Serialize},
     serde_json::{self, Value},
-    std::{borrow::Cow, collections::BTreeMap},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashSet},
+    },
 };
 
+/// Finds charges that repeat every week, month or year, such as
+/// subscriptions, bills and memberships.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct RepeatedTransactions {
-    threshold: f64,
+    /// How much the amounts of one recurring charge may vary from one charge
+    /// to the next, as a percentage. Charges of the same merchant further
+    /// apart than this are counted as separate recurring charges.
+    #[serde(default = "default_amount_tolerance_percent")]
+    amount_tolerance_percent: f64,
+    /// How many times a charge must have happened to count as recurring.
+    #[serde(default = "default_min_occurrences")]
+    min_occurrences: usize,
+    /// Recurring charges that started within this many days of the end of
+    /// the report are flagged as new.
+    #[serde(default = "default_new_within_days")]
+    new_within_days: i64,
 }
 
-impl RepeatedTransactions {
-    pub fn new(threshold: f64) -> RepeatedTransactions {
-        RepeatedTransactions { threshold }
-    }
+fn default_amount_tolerance_percent() -> f64 {
+    10.0
 }
 
-#[derive(Debug, Serialize)]
-struct Report {
-    transactions: Vec<Transaction>,
-    average_date_delta: i64,
+fn default_min_occurrences() -> usize {
+    3
 }
 
-#[derive(Debug)]
-struct NearbyFloatsBucket<F: Float> {
-    high: NotNaN<F>,
-    low: NotNaN<F>,
+fn default_new_within_days() -> i64 {
+    90
 }
 
-impl<F: Float> NearbyFloatsBucket<F> {
-    fn from(low: NotNaN<F>, high: NotNaN<F>) -> NearbyFloatsBucket<F> {
-        NearbyFloatsBucket { high, low }
+impl Default for RepeatedTransactions {
+    fn default() -> RepeatedTransactions {
+        RepeatedTransactions {
+            amount_tolerance_percent: default_amount_tolerance_percent(),
+            min_occurrences: default_min_occurrences(),
+            new_within_days: default_new_within_days(),
+        }
     }
 }
 
-struct NearbyFloatsBuilder<F: Float> {
-    margin: F,
-    buckets: Vec<(NotNaN<F>, NotNaN<F>)>,
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum Period {
+    Weekly,
+    Monthly,
+    Annual,
 }
 
-impl<F: Float> NearbyFloatsBuilder<F> {
-    fn new() -> NearbyFloatsBuilder<F> {
-        NearbyFloatsBuilder {
-            margin: F::epsilon(),
-            buckets: Vec::new(),
+impl Period {
+    /// The period whose length the typical days between charges fits.
+    fn from_days(days: i64) -> Option<Period> {
+        match days {
+            5..=9 => Some(Period::Weekly),
+            26..=35 => Some(Period::Monthly),
+            350..=380 => Some(Period::Annual),
+            _ => None,
         }
     }
 
-    fn margin(mut self, margin: F) -> NearbyFloatsBuilder<F> {
-        self.margin = margin;
-        self
+    fn timeframe(self) -> Timeframe {
+        match self {
+            Period::Weekly => Timeframe::Weeks(1),
+            Period::Monthly => Timeframe::Months(1),
+            Period::Annual => Timeframe::Years(1),
+        }
+    }
+
+    /// How late a charge may be before it counts as missed.
+    fn grace_days(self) -> i64 {
+        match self {
+            Period::Weekly => 3,
+            Period::Monthly => 7,
+            Period::Annual => 31,
+        }
     }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum Status {
+    Active,
+    /// Started recently.
+    New,
+    /// The next charge is overdue, e.g. because it was cancelled.
+    Missed,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PriceIncrease {
+    from: Money,
+    to: Money,
+    date: Date,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecurringCharge {
+    merchant: String,
+    period: Period,
+    /// The amount of the latest charge.
+    amount: Money,
+    status: Status,
+    first: Date,
+    last: Date,
+    next: Date,
+    /// The latest time the amount went up, if it did.
+    price_increase: Option<PriceIncrease>,
+    transactions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepeatsReport {
+    recurring: Vec<RecurringCharge>,
+}
 
-    fn seed(mut self, mut floats: Vec<F>) -> NearbyFloatsBuilder<F> {
-        floats.sort_by(|a, b| NotNaN::from(*a).cmp(&NotNaN::from(*b)));
-        let mut low = floats[0];
-        let mut previous = floats[0];
-        for float in floats {
-            if float > previous + self.margin {
-                self.buckets
-                    .push((NotNaN::from(low), NotNaN::from(previous)));
-                low = float;
+fn median(mut values: Vec<i64>) -> i64 {
+    values.sort();
+    values[values.len() / 2]
+}
+
+impl RepeatedTransactions {
+    /// Splits a merchant's charges into groups of similar amounts, where each
+    /// amount is within the tolerance of the next smaller one.
+    fn amount_bands<'a>(&self, mut charges: Vec<&'a Transaction>) -> Vec<Vec<&'a Transaction>> {
+        charges.sort_by_key(|t| t.amount);
+        let mut bands: Vec<Vec<&Transaction>> = Vec::new();
+        for charge in charges {
+            let starts_band = bands
+                .last()
+                .and_then(|band| band.last())
+                .iter()
+                .all(|previous| {
+                    charge.amount > previous.amount * (1.0 + self.amount_tolerance_percent / 100.0)
+                });
+            if starts_band {
+                bands.push(Vec::new());
+            }
+            if let Some(band) = bands.last_mut() {
+                band.push(charge);
             }
-            previous = float;
         }
-        self.buckets
-            .push((NotNaN::from(low), NotNaN::from(previous)));
+        bands
+    }
+
+    fn recurring(
+        &self,
+        merchant: &str,
+        mut charges: Vec<&Transaction>,
+        end_date: Date,
+    ) -> Option<RecurringCharge> {
+        if charges.len() < self.min_occurrences.max(2) {
+            return None;
+        }
+        charges.sort_by_key(|t| t.date);
+        let period = Period::from_days(median(
+            charges.windows(2).map(|w| w[1].date - w[0].date).collect(),
+        ))?;
+
+        let (first, last) = (charges[0], charges[charges.len() - 1]);
+        let next = last.date + period.timeframe();
+        let status = if end_date - next > period.grace_days() {
+            Status::Missed
+        } else if end_date - first.date <= self.new_within_days {
+            Status::New
+        } else {
+            Status::Active
+        };
+        let price_increase = charges
+            .windows(2)
+            .rev()
+            .find(|w| w[1].amount > w[0].amount)
+            .map(|w| PriceIncrease {
+                from: w[0].amount,
+                to: w[1].amount,
+                date: w[1].date,
+            });
 
-        self
+        Some(RecurringCharge {
+            merchant: merchant.to_owned(),
+            period,
+            amount: last.amount,
+            status,
+            first: first.date,
+            last: last.date,
+            next,
+            price_increase,
+            transactions: charges.iter().map(|t| t.uid()).collect(),
+        })
     }
 
-    fn build(self) -> NearbyFloats<F> {
-        NearbyFloats {
-            buckets: self
-                .buckets
-                .into_iter()
-                .map(|(low, high)| NearbyFloatsBucket::from(low, high))
-                .collect(),
+    fn find<'a, I>(&self, transactions: I, end_date: Date) -> Vec<RecurringCharge>
+    where
+        I: Iterator<Item = Cow<'a, Transaction>>,
+    {
+        // The parts of a split transaction come one after another, so they
+        // are put back together to be counted as the one charge they were.
+        let mut charges: Vec<Cow<Transaction>> = Vec::new();
+        for t in transactions.filter(|t| t.transaction_type.is_debit()) {
+            match charges.last_mut() {
+                Some(last) if last.uid() == t.uid() => last.to_mut().amount += t.amount,
+                _ => charges.push(t),
+            }
+        }
+        let mut by_merchant = BTreeMap::new();
+        for transaction in &charges {
+            by_merchant
+                .entry(transaction.merchant())
+                .or_insert_with(Vec::new)
+                .push(transaction.as_ref());
+        }
+
+        let mut recurring = Vec::new();
+        for (merchant, charges) in by_merchant {
+            for band in self.amount_bands(charges) {
+                recurring.extend(self.recurring(&merchant, band, end_date));
+            }
         }
+        recurring
     }
-}
 
-#[derive(Debug)]
-struct NearbyFloats<F: Float> {
-    buckets: Vec<NearbyFloatsBucket<F>>,
+    /// The uids of every charge that is part of a recurring charge.
+    pub fn recurring_uids(&self, transactions: &[Transaction]) -> HashSet<String> {
+        let end_date = match transactions.iter().map(|t| t.date).max() {
+            Some(end_date) => end_date,
+            None => return HashSet::new(),
+        };
+        self.find(transactions.iter().map(Cow::Borrowed), end_date)
+            .into_iter()
+            .flat_map(|charge| charge.transactions)
+            .collect()
+    }
 }
 
 impl Reporter for RepeatedTransactions {
-    fn report<'a, T>(&self, transactions: T) -> Value
+    fn report<'a, I>(&self, transactions: I, end_date: Date) -> Value
     where
-        T: Iterator<Item = Cow<'a, Transaction>> + Clone,
+        I: Iterator<Item = Cow<'a, Transaction>>,
     {
-        let finder = NearbyFloatsBuilder::new()
-            .margin(10.)
-            .seed(transactions.clone().map(|t| t.amount).collect())
-            .build();
-        println!("buckets: {:?}", finder);
-        let mut seen = BTreeMap::new();
-        for transaction in transactions {
-            let transaction = transaction.into_owned();
-            if transaction.amount < self.threshold {
-                continue;
-            }
-            (*seen
-                .entry((
-                    transaction.description.clone(),
-                    transaction.transaction_type,
-                ))
-                .or_insert_with(|| Vec::new()))
-            .push(transaction);
-        }
-        let seen = seen
-            .into_iter()
-            .filter_map(|((amt, transaction_type), transactions)| {
-                if transactions.len() > 2 {
-                    let (count, total) = transactions
-                        .windows(2)
-                        .map(|w| w[1].date - w[0].date)
-                        .fold((0, 0), |curr, d| (curr.0 + 1, curr.1 + d));
-                    Some((
-                        format!("{:?} {:?}", transaction_type, amt),
-                        Report {
-                            transactions,
-                            average_date_delta: total / count,
-                        },
-                    ))
-                } else {
-                    None
-                }
-            })
-            .collect::<BTreeMap<_, _>>();
-        serde_json::to_value(seen).expect("couldn't convert")
+        let recurring = self.find(transactions, end_date);
+        serde_json::to_value(RepeatsReport { recurring })
+            .expect("Couldn't serialize repeated transactions")
     }
 
     fn key(&self) -> Option<String> {
         Some("repeated_transactions".to_owned())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::loading::TransactionType};
+
+    fn charge(description: &str, date: Date, amount: &str) -> Transaction {
+        Transaction {
+            id: format!("{} {}", description, date.uid()),
+            date,
+            description: description.to_owned(),
+            amount: amount.parse().unwrap(),
+            transaction_type: TransactionType::Debit,
+            ..Default::default()
+        }
+    }
+
+    fn monthly(description: &str, start: Date, months: i64, amount: &str) -> Vec<Transaction> {
+        (0..months)
+            .map(|i| charge(description, start + Timeframe::Months(i), amount))
+            .collect()
+    }
+
+    #[test]
+    fn finds_subscriptions() {
+        let mut transactions = Vec::new();
+        transactions.extend(monthly(
+            "NETFLIX.COM 866-579",
+            Date::ymd(2018, 7, 3),
+            4,
+            "12.99",
+        ));
+        transactions.extend(monthly(
+            "NETFLIX.COM 866-580",
+            Date::ymd(2018, 11, 3),
+            8,
+            "13.99",
+        ));
+        transactions.extend(monthly("SPOTIFY USA", Date::ymd(2018, 7, 10), 6, "9.99"));
+        transactions.extend(monthly("SPOTIFY USA", Date::ymd(2018, 9, 12), 3, "4.99"));
+        transactions.extend((0..6).map(|i| {
+            charge(
+                "MEAL KIT",
+                Date::ymd(2019, 5, 1) + Timeframe::Weeks(i),
+                "60",
+            )
+        }));
+        transactions
+            .extend((0..3).map(|i| charge("GROCERY STORE", Date::ymd(2019, 1, 1 + 12 * i), "80")));
+        transactions.sort_by_key(|t| t.date);
+
+        let reporter: RepeatedTransactions = toml::from_str("").unwrap();
+        let report = reporter.report(
+            transactions.iter().map(Cow::Borrowed),
+            Date::ymd(2019, 6, 10),
+        );
+        let report: Vec<_> = report["recurring"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["merchant"].as_str().unwrap(),
+                    r["period"].as_str().unwrap(),
+                    r["amount"].as_str().unwrap(),
+                    r["status"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            report,
+            vec![
+                ("MEAL KIT", "Weekly", "60.00", "New"),
+                ("NETFLIX COM", "Monthly", "13.99", "Active"),
+                ("SPOTIFY USA", "Monthly", "4.99", "Missed"),
+                ("SPOTIFY USA", "Monthly", "9.99", "Missed"),
+            ]
+        );
+
+        let netflix = reporter.recurring(
+            "NETFLIX COM",
+            transactions
+                .iter()
+                .filter(|t| t.description.starts_with("NETFLIX"))
+                .collect(),
+            Date::ymd(2019, 6, 10),
+        );
+        assert_eq!(
+            netflix.unwrap().price_increase,
+            Some(PriceIncrease {
+                from: "12.99".parse().unwrap(),
+                to: "13.99".parse().unwrap(),
+                date: Date::ymd(2018, 11, 3),
+            })
+        );
+    }
+
+    #[test]
+    fn split_charges_count_once() {
+        let transactions = monthly("COSTCO MEMBERSHIP", Date::ymd(2018, 1, 15), 6, "60")
+            .iter()
+            .flat_map(|t| {
+                let mut household = t.clone();
+                household.amount = "40".parse().unwrap();
+                let mut groceries = t.clone();
+                groceries.amount = "20".parse().unwrap();
+                vec![household, groceries]
+            })
+            .collect::<Vec<_>>();
+
+        let reporter: RepeatedTransactions = toml::from_str("").unwrap();
+        let report = reporter.report(
+            transactions.iter().map(Cow::Borrowed),
+            Date::ymd(2018, 6, 20),
+        );
+        assert_eq!(report["recurring"].as_array().unwrap().len(), 1);
+        assert_eq!(report["recurring"][0]["amount"], "60.00");
+        assert_eq!(report["recurring"][0]["status"], "Active");
+    }
+}
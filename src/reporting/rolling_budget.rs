@@ -44,7 +44,7 @@ pub struct RollingBudgetReport {
 
 impl RollingBudget {
     fn should_split(&self, transaction: &Transaction) -> bool {
-        transaction.person == self.split
+        transaction.person == self.split || !transaction.owner_shares.is_empty()
     }
 
     fn should_include(&self, transaction: &Transaction) -> bool {
@@ -65,7 +65,13 @@ impl RollingBudget {
         transaction: &Transaction,
         amounts: &HashMap<String, Money>,
     ) -> HashMap<String, Money> {
-        if self.should_split(transaction) {
+        if !transaction.owner_shares.is_empty() {
+            transaction
+                .owner_amounts()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect()
+        } else if self.should_split(transaction) {
             RollingBudget::proportions(amounts)
                 .into_iter()
                 .map(|(k, v)| (k.to_string(), transaction.amount * v))
@@ -167,7 +173,10 @@ impl Reporter for RollingBudget {
                         _ => {}
                     }
                 }
-                report.transactions.push(transaction.uid());
+                let uid = transaction.uid();
+                if report.transactions.last() != Some(&uid) {
+                    report.transactions.push(uid);
+                }
                 if let Some(ref mut ts) = report.timeseries {
                     ts.add(transaction.date, report.budgets.clone());
                 }
@@ -0,0 +1,402 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use {
+    crate::{
+        loading::{Money, Transaction, TransactionType},
+        reporting::{config::ReportOptions, timeseries::Timeseries, Reporter},
+    },
+    budgetronlib::fintime::Date,
+    serde::{Deserialize, Serialize},
+    serde_json::{self, Value},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet, HashMap},
+    },
+};
+
+/// Works out who owes whom for the expenses owners pay for each other.
+///
+/// Whoever owns the account a transaction is paid from paid for it. A
+/// transaction owned by `split` is shared between the owners by `shares`,
+/// and one owned by someone other than the payer is owed by them in full.
+/// Transactions with their own owner shares are shared by those instead.
+/// Transfers between the accounts of different owners settle up.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Settlement {
+    /// The owner of the transactions that are shared.
+    split: String,
+    /// Which owner pays from each account. Transactions in other accounts
+    /// are left out.
+    paid_by: HashMap<String, String>,
+    /// How shared transactions are divided from each date on. Transactions
+    /// before the first date are left out, and without any dates they are
+    /// divided equally.
+    #[serde(default)]
+    shares: BTreeMap<Date, Shares>,
+    #[serde(default)]
+    options: ReportOptions,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Shares {
+    Basis(ShareBasis),
+    /// Fixed ratios between the owners, e.g. `{ Person1 = 3, Person2 = 2 }`.
+    Ratios(HashMap<String, f64>),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareBasis {
+    Equal,
+    /// In proportion to each owner's income until the shares change.
+    Income,
+}
+
+/// A payment that settles up what is owed.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Payment {
+    from: String,
+    to: String,
+    amount: Money,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettlementReport {
+    /// What each owner is owed, or owes when negative.
+    balances: BTreeMap<String, Money>,
+    /// The payments that would settle everyone up.
+    payments: Vec<Payment>,
+    /// The transfers between owners that settled up.
+    settle_ups: Vec<String>,
+    transactions: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeseries: Option<Timeseries<BTreeMap<String, Money>>>,
+}
+
+/// Divides an amount by weights. The last owner gets whatever rounding
+/// leaves, so that the parts always add up to the amount.
+fn allocate<'a>(amount: Money, weights: &[(&'a str, f64)]) -> Vec<(&'a str, Money)> {
+    let total = weights.iter().map(|&(_, w)| w).sum::<f64>();
+    let mut left = amount;
+    let mut parts = weights
+        .iter()
+        .map(|&(owner, weight)| {
+            let part = amount * (weight / total);
+            left -= part;
+            (owner, part)
+        })
+        .collect::<Vec<_>>();
+    if let Some(last) = parts.last_mut() {
+        last.1 += left;
+    }
+    parts
+}
+
+/// The payments that settle up the balances, from whoever owes the most to
+/// whoever is owed the most, until everyone is even. That takes at most one
+/// payment fewer than there are owners.
+fn settle_up(balances: &BTreeMap<String, Money>) -> Vec<Payment> {
+    let mut owing = Vec::new();
+    let mut owed = Vec::new();
+    for (owner, &balance) in balances {
+        if balance.is_negative() {
+            owing.push((owner.clone(), -balance));
+        } else if balance != Money::zero() {
+            owed.push((owner.clone(), balance));
+        }
+    }
+
+    let mut payments = Vec::new();
+    while !owing.is_empty() && !owed.is_empty() {
+        owing.sort_by_key(|&(_, amount)| amount);
+        owed.sort_by_key(|&(_, amount)| amount);
+        let (from, debt) = owing.pop().expect("Nobody owes");
+        let (to, credit) = owed.pop().expect("Nobody is owed");
+        let amount = if debt < credit { debt } else { credit };
+        if debt > amount {
+            owing.push((from.clone(), debt - amount));
+        }
+        if credit > amount {
+            owed.push((to.clone(), credit - amount));
+        }
+        payments.push(Payment { from, to, amount });
+    }
+    payments
+}
+
+impl Settlement {
+    fn owners(&self) -> BTreeSet<&str> {
+        let mut owners = self
+            .paid_by
+            .values()
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+        for shares in self.shares.values() {
+            if let Shares::Ratios(ratios) = shares {
+                owners.extend(ratios.keys().map(String::as_str));
+            }
+        }
+        owners
+    }
+
+    fn payer(&self, account: &str) -> Option<&str> {
+        self.paid_by.get(account).map(String::as_str)
+    }
+
+    /// When the shares in effect on the date started, and what they are.
+    fn shares_on(&self, date: Date) -> Option<(Option<Date>, Option<&Shares>)> {
+        if self.shares.is_empty() {
+            return Some((None, None));
+        }
+        self.shares
+            .range(..=date)
+            .next_back()
+            .map(|(&start, shares)| (Some(start), Some(shares)))
+    }
+
+    /// Each owner's income while each of the shares were in effect.
+    fn incomes<'a>(
+        &self,
+        transactions: &'a [Cow<'a, Transaction>],
+    ) -> HashMap<Option<Date>, HashMap<&'a str, Money>> {
+        let owners = self.owners();
+        let mut incomes: HashMap<_, HashMap<_, _>> = HashMap::new();
+        for t in transactions {
+            if t.transaction_type != TransactionType::Credit || !owners.contains(&*t.person) {
+                continue;
+            }
+            if let Some((start, _)) = self.shares_on(t.date) {
+                *incomes
+                    .entry(start)
+                    .or_default()
+                    .entry(t.person.as_str())
+                    .or_insert_with(Money::zero) += t.amount;
+            }
+        }
+        incomes
+    }
+
+    fn weights<'a>(
+        shares: Option<&'a Shares>,
+        owners: &BTreeSet<&'a str>,
+        income: Option<&HashMap<&'a str, Money>>,
+    ) -> Vec<(&'a str, f64)> {
+        let equal = || owners.iter().map(|&owner| (owner, 1.0)).collect();
+        match shares {
+            Some(Shares::Ratios(ratios)) => ratios
+                .iter()
+                .map(|(owner, &ratio)| (owner.as_str(), ratio))
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .collect(),
+            Some(Shares::Basis(ShareBasis::Income)) => match income {
+                Some(income) if income.values().any(|i| *i > Money::zero()) => owners
+                    .iter()
+                    .map(|&owner| {
+                        let earned = income.get(owner).cloned().unwrap_or_else(Money::zero);
+                        (owner, earned.to_f64())
+                    })
+                    .collect(),
+                _ => equal(),
+            },
+            Some(Shares::Basis(ShareBasis::Equal)) | None => equal(),
+        }
+    }
+}
+
+impl Reporter for Settlement {
+    fn report<'a, I>(&self, transactions: I, _: Date) -> Value
+    where
+        I: Iterator<Item = Cow<'a, Transaction>>,
+    {
+        let transactions = transactions.collect::<Vec<_>>();
+        let owners = self.owners();
+        let incomes = self.incomes(&transactions);
+
+        let mut report = SettlementReport {
+            balances: owners
+                .iter()
+                .map(|&owner| (owner.to_owned(), Money::zero()))
+                .collect(),
+            payments: Vec::new(),
+            settle_ups: Vec::new(),
+            transactions: Vec::new(),
+            timeseries: if self.options.include_graph {
+                Some(Timeseries::new())
+            } else {
+                None
+            },
+        };
+
+        for t in &transactions {
+            let (start, shares) = match self.shares_on(t.date) {
+                Some(shares) => shares,
+                None => continue,
+            };
+            let payer = match self.payer(&t.account_name) {
+                Some(payer) => payer,
+                None => continue,
+            };
+
+            let (paid, owed) = match t.transaction_type {
+                TransactionType::Transfer => {
+                    let payee = t
+                        .transfer_destination_account
+                        .as_ref()
+                        .and_then(|account| self.payer(account));
+                    match payee {
+                        Some(payee) if payee != payer => {
+                            report.settle_ups.push(t.uid());
+                            (t.amount, vec![(payee, t.amount)])
+                        }
+                        _ => continue,
+                    }
+                }
+                TransactionType::Debit | TransactionType::Credit => {
+                    let owed = if !t.owner_shares.is_empty() {
+                        t.owner_amounts()
+                    } else if t.person == self.split {
+                        let weights = Settlement::weights(shares, &owners, incomes.get(&start));
+                        allocate(t.amount, &weights)
+                    } else if t.person != payer && owners.contains(&*t.person) {
+                        vec![(t.person.as_str(), t.amount)]
+                    } else {
+                        continue;
+                    };
+                    if t.transaction_type.is_credit() {
+                        (
+                            -t.amount,
+                            owed.into_iter().map(|(owner, a)| (owner, -a)).collect(),
+                        )
+                    } else {
+                        (t.amount, owed)
+                    }
+                }
+            };
+
+            *report
+                .balances
+                .entry(payer.to_owned())
+                .or_insert_with(Money::zero) += paid;
+            for (owner, amount) in owed {
+                *report
+                    .balances
+                    .entry(owner.to_owned())
+                    .or_insert_with(Money::zero) -= amount;
+            }
+            let uid = t.uid();
+            if report.transactions.last() != Some(&uid) {
+                report.transactions.push(uid);
+            }
+            if let Some(ref mut ts) = report.timeseries {
+                ts.add(t.date, report.balances.clone());
+            }
+        }
+        report.payments = settle_up(&report.balances);
+
+        serde_json::to_value(&report).expect("Couldn't serialize settlement")
+    }
+
+    fn key(&self) -> Option<String> {
+        Some("settlement".to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(
+        month: u32,
+        amount: i64,
+        transaction_type: TransactionType,
+        account: &str,
+        person: &str,
+    ) -> Transaction {
+        Transaction {
+            id: format!("{}-{}-{}", account, month, amount),
+            date: Date::ymd(2019, month as i32, 1),
+            amount: Money::from_i64(amount),
+            transaction_type,
+            account_name: account.to_owned(),
+            person: person.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn run(config: &str, transactions: &[Transaction]) -> Value {
+        let settlement: Settlement = toml::from_str(config).expect("Unable to parse settlement");
+        settlement.report(
+            transactions.iter().map(Cow::Borrowed),
+            Date::ymd(2019, 12, 31),
+        )
+    }
+
+    #[test]
+    fn shares_change_over_time() {
+        use self::TransactionType::*;
+        let mut transfer = t(5, 10, Transfer, "Zach Checking", "Zach");
+        transfer.transfer_destination_account = Some("Jane Checking".to_owned());
+        let transactions = vec![
+            t(1, 100, Debit, "Zach Visa", "Joint"),
+            t(2, 300, Debit, "Jane Checking", "Joint"),
+            t(3, 40, Debit, "Zach Visa", "Jane"),
+            t(3, 30, Debit, "Zach Visa", "Zach"),
+            t(3, 20, Credit, "Zach Visa", "Joint"),
+            t(4, 3000, Credit, "Zach Checking", "Zach"),
+            t(4, 1000, Credit, "Jane Checking", "Jane"),
+            t(4, 400, Debit, "Zach Visa", "Joint"),
+            transfer,
+            t(6, 10, Debit, "Cash", "Joint"),
+        ];
+        let report = run(
+            r#"
+            split = "Joint"
+            paid_by = { "Zach Visa" = "Zach", "Zach Checking" = "Zach", "Jane Checking" = "Jane" }
+            [shares]
+            "01/01/2019" = "equal"
+            "02/01/2019" = { Zach = 2, Jane = 1 }
+            "04/01/2019" = "income"
+            "#,
+            &transactions,
+        );
+
+        // Zach: +50, -200, +40, -6.67 for his share of the refund, +100 with
+        // three quarters of the income, and +10 settled up.
+        assert_eq!(report["balances"]["Zach"], "-6.67");
+        assert_eq!(report["balances"]["Jane"], "6.67");
+        assert_eq!(report["settle_ups"][0], "Zach Checking-5-10");
+        assert_eq!(report["payments"].as_array().unwrap().len(), 1);
+        assert_eq!(report["payments"][0]["from"], "Zach");
+        assert_eq!(report["payments"][0]["to"], "Jane");
+        assert_eq!(report["payments"][0]["amount"], "6.67");
+    }
+
+    #[test]
+    fn fewest_payments() {
+        let balances = [("A", 60), ("B", -50), ("C", 10), ("D", -20)]
+            .iter()
+            .map(|&(owner, amount)| (owner.to_owned(), Money::from_i64(amount)))
+            .collect();
+        let payments = settle_up(&balances)
+            .into_iter()
+            .map(|p| (p.from, p.to, p.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            payments,
+            vec![
+                ("B".to_owned(), "A".to_owned(), Money::from_i64(50)),
+                ("D".to_owned(), "A".to_owned(), Money::from_i64(10)),
+                ("D".to_owned(), "C".to_owned(), Money::from_i64(10)),
+            ]
+        );
+    }
+}
@@ -7,12 +7,20 @@
 // except according to those terms.
 
 use {
-    budgetron::{processing::ConfiguredProcessors, reporting::ConfiguredReports},
+    budgetron::{
+        loading::ConfiguredImporters,
+        processing::{ConfiguredBalances, ConfiguredProcessors},
+        reporting::ConfiguredReports,
+    },
     budgetronlib::config,
 };
 
 #[test]
 fn test_loading_budgetronrc_example() {
+    let _: ConfiguredImporters =
+        config::load_cfg("budgetronrc.example.toml").expect("Configured Importers failed to load");
+    let _: ConfiguredBalances =
+        config::load_cfg("budgetronrc.example.toml").expect("Configured Balances failed to load");
     let _: ConfiguredProcessors =
         config::load_cfg("budgetronrc.example.toml").expect("Failed to load configured processors");
     let _: ConfiguredReports =
@@ -1,18 +1,36 @@
 import ByTimeframe from "components/ByTimeframe";
 import Cashflow from "components/Cashflow";
 import Categories from "components/Categories";
+import DroppedTransactions from "components/DroppedTransactions";
+import DuplicateCharges from "components/DuplicateCharges";
 import IncomeExpenseRatio from "components/IncomeExpenseRatio";
+import OwnerSpending from "components/OwnerSpending";
+import RepeatedTransactions from "components/RepeatedTransactions";
 import RollingBudget from "components/RollingBudget";
+import Settlement from "components/Settlement";
 import Chip from "material-ui/Chip";
 import * as React from "react";
-import { Report, TimedReportData, Transaction } from "util/data";
+import {
+  DroppedTransaction,
+  Report,
+  TimedReportData,
+  Transaction,
+} from "util/data";
 
 import Page from "components/Page";
 
 import * as style from "./style.scss";
 
 const componentConfig = (
-  type: "RollingBudget" | "Cashflow" | "Categories" | "IncomeExpenseRatio",
+  type:
+    | "RollingBudget"
+    | "Cashflow"
+    | "Categories"
+    | "IncomeExpenseRatio"
+    | "RepeatedTransactions"
+    | "DuplicateCharges"
+    | "Settlement"
+    | "OwnerSpending",
 ) => {
   const config: {
     Component: string | React.ComponentType<any>;
@@ -31,6 +49,14 @@ const componentConfig = (
   } else if (type === "IncomeExpenseRatio") {
     config.Component = IncomeExpenseRatio;
     config.count = 100;
+  } else if (type === "RepeatedTransactions") {
+    config.Component = RepeatedTransactions;
+  } else if (type === "DuplicateCharges") {
+    config.Component = DuplicateCharges;
+  } else if (type === "Settlement") {
+    config.Component = Settlement;
+  } else if (type === "OwnerSpending") {
+    config.Component = OwnerSpending;
   }
 
   return config;
@@ -101,6 +127,7 @@ const SimpleReports = (props: ISimpleReportsProps) => (
 interface IBudgetronProps {
   data: Report[];
   transactions: Map<string, Transaction>;
+  dropped: Map<string, DroppedTransaction>;
 }
 
 interface IBudgetronState {
@@ -151,6 +178,11 @@ class Budgetron extends React.Component<IBudgetronProps, IBudgetronState> {
           {this.getChip("year", "By Year")}
         </div>
         <SimpleReports {...this.props} />
+        {this.props.dropped.size > 0 ? (
+          <Page className={style.report} title="Dropped Transactions">
+            <DroppedTransactions dropped={this.props.dropped} />
+          </Page>
+        ) : null}
         <TimeframeReports
           display={this.state.month}
           timeframe="Month"
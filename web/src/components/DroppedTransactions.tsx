@@ -0,0 +1,35 @@
+import * as React from "react";
+import { DroppedTransaction, Transaction } from "util/data";
+
+import * as style from "components/Transactions/style.scss";
+
+const COLUMNS = ["date", "amount", "description", "accountName"];
+
+interface IProps {
+  dropped: Map<string, DroppedTransaction>;
+}
+
+const DroppedTransactions = (props: IProps) => (
+  <table className={style.table}>
+    <thead>
+      <tr>
+        {COLUMNS.map((c) => <th key={c}>{Transaction.transactionName(c)}</th>)}
+        <th>Processor</th>
+        <th>Reason</th>
+      </tr>
+    </thead>
+    <tbody>
+      {Array.from(props.dropped.entries())
+        .sort(([a], [b]) => (a < b ? 1 : a > b ? -1 : 0))
+        .map(([uid, { transaction, processor, reason }]) => (
+          <tr key={uid} className={style.normal_row}>
+            {COLUMNS.map((c) => <td key={c}>{transaction.render(c)}</td>)}
+            <td>{processor}</td>
+            <td>{reason}</td>
+          </tr>
+        ))}
+    </tbody>
+  </table>
+);
+
+export default DroppedTransactions;
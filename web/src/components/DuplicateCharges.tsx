@@ -0,0 +1,70 @@
+import Money from "components/Money";
+import Transactions from "components/Transactions";
+import * as React from "react";
+import { DuplicateChargesData, Transaction } from "util/data";
+
+interface IProps {
+  data: DuplicateChargesData;
+  transactions: Map<string, Transaction>;
+}
+
+interface IState {
+  expanded: { [row: number]: boolean };
+}
+
+export default class DuplicateCharges extends React.Component<IProps, IState> {
+  constructor(props: IProps) {
+    super(props);
+
+    this.state = {
+      expanded: {},
+    };
+  }
+
+  public toggleExpanded(row: number) {
+    const { expanded } = this.state;
+    expanded[row] = !expanded[row];
+    this.setState({ expanded });
+  }
+
+  public render() {
+    return (
+      <table>
+        <tbody>
+          {this.props.data.duplicates.map((charge, row) => [
+            <tr key={row}>
+              <td>
+                <button onClick={() => this.toggleExpanded(row)}>
+                  {charge.merchant}
+                </button>
+              </td>
+              <td>{charge.account}</td>
+              <td>
+                <Money amount={charge.amount} invert={true} />
+              </td>
+              <td>{charge.dates.join(", ")}</td>
+              <td>
+                Extra: <Money amount={charge.extra} invert={true} />
+              </td>
+            </tr>,
+            <tr key={`${row} transactions`}>
+              {this.state.expanded[row] ? (
+                <Transactions
+                  transaction_ids={charge.transactions}
+                  transactions={this.props.transactions}
+                />
+              ) : null}
+            </tr>,
+          ])}
+          <tr>
+            <td>Total extra</td>
+            <td />
+            <td>
+              <Money amount={this.props.data.totalExtra} invert={true} />
+            </td>
+          </tr>
+        </tbody>
+      </table>
+    );
+  }
+}
@@ -0,0 +1,61 @@
+import Money from "components/Money";
+import Transactions from "components/Transactions";
+import * as React from "react";
+import { OwnerSpendingData, Transaction } from "util/data";
+
+interface IProps {
+  data: OwnerSpendingData;
+  transactions: Map<string, Transaction>;
+}
+
+interface IState {
+  show: string;
+}
+
+export default class OwnerSpending extends React.Component<IProps, IState> {
+  constructor(props: IProps) {
+    super(props);
+
+    this.state = {
+      show: "",
+    };
+  }
+
+  public toggleTable(owner: string) {
+    this.setState({ show: this.state.show === owner ? "" : owner });
+  }
+
+  public render() {
+    return (
+      <div>
+        {[...this.props.data.owners.entries()].map(([owner, entry]) => (
+          <div key={owner}>
+            <button onClick={() => this.toggleTable(owner)}>{owner}</button>:{" "}
+            spent <Money amount={entry.spent} invert={true} />, received{" "}
+            <Money amount={entry.received} />
+            {this.state.show === owner ? (
+              <div>
+                <table>
+                  <tbody>
+                    {[...entry.categories.entries()].map(([category, amount]) => (
+                      <tr key={category}>
+                        <td>{category}</td>
+                        <td>
+                          <Money amount={amount} invert={true} />
+                        </td>
+                      </tr>
+                    ))}
+                  </tbody>
+                </table>
+                <Transactions
+                  transaction_ids={entry.transactions}
+                  transactions={this.props.transactions}
+                />
+              </div>
+            ) : null}
+          </div>
+        ))}
+      </div>
+    );
+  }
+}
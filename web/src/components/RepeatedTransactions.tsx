@@ -0,0 +1,75 @@
+import Money from "components/Money";
+import Transactions from "components/Transactions";
+import * as React from "react";
+import { RecurringCharge, RepeatedTransactionsData, Transaction } from "util/data";
+
+const Flags = ({ charge }: { charge: RecurringCharge }) => (
+  <>
+    {charge.status !== "Active" ? <b>{charge.status} </b> : null}
+    {charge.priceIncrease ? (
+      <span>
+        Up from <Money amount={charge.priceIncrease.from} invert={true} /> on{" "}
+        {charge.priceIncrease.date}
+      </span>
+    ) : null}
+  </>
+);
+
+interface IProps {
+  data: RepeatedTransactionsData;
+  transactions: Map<string, Transaction>;
+}
+
+interface IState {
+  expanded: { [row: number]: boolean };
+}
+
+export default class RepeatedTransactions extends React.Component<IProps, IState> {
+  constructor(props: IProps) {
+    super(props);
+
+    this.state = {
+      expanded: {},
+    };
+  }
+
+  public toggleExpanded(row: number) {
+    const { expanded } = this.state;
+    expanded[row] = !expanded[row];
+    this.setState({ expanded });
+  }
+
+  public render() {
+    return (
+      <table>
+        <tbody>
+          {this.props.data.recurring.map((charge, row) => [
+            <tr key={row}>
+              <td>
+                <button onClick={() => this.toggleExpanded(row)}>
+                  {charge.merchant}
+                </button>
+              </td>
+              <td>{charge.period}</td>
+              <td>
+                <Money amount={charge.amount} invert={true} />
+              </td>
+              <td>Next: {charge.next}</td>
+              <td>
+                <Flags charge={charge} />
+              </td>
+            </tr>,
+            <tr key={`${row} transactions`}>
+              {this.state.expanded[row] ? (
+                <Transactions
+                  transaction_ids={charge.transactions}
+                  transactions={this.props.transactions}
+                />
+              ) : null}
+            </tr>,
+          ])}
+        </tbody>
+      </table>
+    );
+  }
+}
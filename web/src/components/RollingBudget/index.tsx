@@ -61,12 +61,17 @@ export default class RollingBudget extends React.Component<IProps, IState> {
               transaction_ids={this.props.data.transactions}
               transactions={this.props.transactions}
               filter={([, t]) =>
-                t.person === person ||
-                t.person === this.props.report.config.split
+                t.ownerShares.length > 0
+                  ? t.ownerShares.some((share) => share.person === person)
+                  : t.person === person ||
+                    t.person === this.props.report.config.split
               }
               transform={(t: Transaction) => {
-                if (t.person === this.props.report.config.split) {
-                  const proportion = this.proportions(this.amounts(t.date))[person];
+                const share = t.ownerShares.find((s) => s.person === person);
+                if (share || t.person === this.props.report.config.split) {
+                  const proportion = share
+                    ? share.percent / 100
+                    : this.proportions(this.amounts(t.date))[person];
                   let amount: number;
                   if (typeof t.amount === "string") {
                     amount = parseFloat(t.amount);
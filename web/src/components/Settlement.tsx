@@ -0,0 +1,59 @@
+import Money from "components/Money";
+import Transactions from "components/Transactions";
+import * as React from "react";
+import { SettlementData, Transaction } from "util/data";
+
+interface IProps {
+  data: SettlementData;
+  transactions: Map<string, Transaction>;
+}
+
+interface IState {
+  show: boolean;
+}
+
+export default class Settlement extends React.Component<IProps, IState> {
+  constructor(props: IProps) {
+    super(props);
+
+    this.state = {
+      show: false,
+    };
+  }
+
+  public render() {
+    const { data } = this.props;
+    return (
+      <div>
+        {[...data.balances.entries()].map(([owner, balance]) => (
+          <div key={owner}>
+            {owner}: <Money amount={balance} />
+          </div>
+        ))}
+        <table>
+          <tbody>
+            {data.payments.map((payment, row) => (
+              <tr key={row}>
+                <td>{payment.from}</td>
+                <td>pays</td>
+                <td>{payment.to}</td>
+                <td>
+                  <Money amount={payment.amount} />
+                </td>
+              </tr>
+            ))}
+          </tbody>
+        </table>
+        <button onClick={() => this.setState({ show: !this.state.show })}>
+          Transactions
+        </button>
+        {this.state.show ? (
+          <Transactions
+            transaction_ids={data.transactions}
+            transactions={this.props.transactions}
+          />
+        ) : null}
+      </div>
+    );
+  }
+}
@@ -17,6 +17,10 @@ const COLUMNS = [
   "labels",
   "notes",
   "tags",
+  "splits",
+  "refundedBy",
+  "amortizedFrom",
+  "provenance",
 ];
 
 interface IDetailsTableIProps {
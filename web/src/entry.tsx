@@ -4,6 +4,8 @@ import "normalize.css";
 import * as React from "react";
 import * as ReactDOM from "react-dom";
 import {
+  DroppedTransaction,
+  parseDroppedTransactions,
   parseReports,
   parseTransactions,
   Report,
@@ -13,6 +15,7 @@ import {
 const App = (props: {
   data: Report[];
   transactions: Map<string, Transaction>;
+  dropped: Map<string, DroppedTransaction>;
 }) => (
     <MuiThemeProvider>
       <Budgetron {...props} />
@@ -21,10 +24,11 @@ const App = (props: {
 
 let data: Report[] = [];
 let transactions = new Map();
+let dropped = new Map();
 const render = () => {
   const root = document.getElementById("root");
   if (root) {
-    ReactDOM.render(<App data={data} transactions={transactions} />, root);
+    ReactDOM.render(<App data={data} transactions={transactions} dropped={dropped} />, root);
   }
 };
 
@@ -38,6 +42,7 @@ fetch("/__/data.json").then((reports) =>
 fetch("/__/transactions.json").then((reports) =>
   reports.json().then((json) => {
     transactions = parseTransactions(json);
+    dropped = parseDroppedTransactions(json);
     render();
   }),
 );
@@ -0,0 +1,56 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+export class DuplicateCharge {
+  public merchant: string;
+  public account: string;
+  public amount: string;
+  public dates: string[];
+  public extra: string;
+  public transactions: string[];
+
+  constructor(data: { [part: string]: any }) {
+    if (typeof data.merchant === "string") { this.merchant = data.merchant; }
+    if (typeof data.account === "string") { this.account = data.account; }
+    if (typeof data.amount === "string") { this.amount = data.amount; }
+    if (typeof data.extra === "string") { this.extra = data.extra; }
+    this.dates = [];
+    if (Array.isArray(data.dates)) {
+      data.dates.forEach((d) => {
+        if (typeof d === "string") {
+          this.dates.push(d);
+        }
+      });
+    }
+    this.transactions = [];
+    if (Array.isArray(data.transactions)) {
+      data.transactions.forEach((t) => {
+        if (typeof t === "string") {
+          this.transactions.push(t);
+        }
+      });
+    }
+  }
+}
+
+export class DuplicateChargesData {
+  public duplicates: DuplicateCharge[];
+  public totalExtra: string;
+
+  constructor(data: { [part: string]: any }) {
+    this.duplicates = [];
+    if (Array.isArray(data.duplicates)) {
+      data.duplicates.forEach((d) => {
+        if (d && typeof d === "object") {
+          this.duplicates.push(new DuplicateCharge(d));
+        }
+      });
+    }
+    if (typeof data.total_extra === "string") { this.totalExtra = data.total_extra; }
+  }
+}
@@ -0,0 +1,50 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+export class OwnerEntry {
+  public spent: string;
+  public received: string;
+  public categories: Map<string, string>;
+  public transactions: string[];
+
+  constructor(data: { [part: string]: any }) {
+    if (typeof data.spent === "string") { this.spent = data.spent; }
+    if (typeof data.received === "string") { this.received = data.received; }
+    this.categories = new Map();
+    if (data.categories && typeof data.categories === "object") {
+      Object.entries(data.categories).forEach(([k, v]) => {
+        if (typeof k === "string" && typeof v === "string") {
+          this.categories.set(k, v);
+        }
+      });
+    }
+    this.transactions = [];
+    if (Array.isArray(data.transactions)) {
+      data.transactions.forEach((t) => {
+        if (typeof t === "string") {
+          this.transactions.push(t);
+        }
+      });
+    }
+  }
+}
+
+export class OwnerSpendingData {
+  public owners: Map<string, OwnerEntry>;
+
+  constructor(data: { [part: string]: any }) {
+    this.owners = new Map();
+    if (data.owners && typeof data.owners === "object") {
+      Object.entries(data.owners).forEach(([k, v]) => {
+        if (typeof k === "string" && v && typeof v === "object") {
+          this.owners.set(k, new OwnerEntry(v));
+        }
+      });
+    }
+  }
+}
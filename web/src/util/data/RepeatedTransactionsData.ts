@@ -0,0 +1,67 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+export interface IPriceIncrease {
+  from: string;
+  to: string;
+  date: string;
+}
+
+export class RecurringCharge {
+  public merchant: string;
+  public period: string;
+  public amount: string;
+  public status: string;
+  public first: string;
+  public last: string;
+  public next: string;
+  public priceIncrease?: IPriceIncrease;
+  public transactions: string[];
+
+  constructor(data: { [part: string]: any }) {
+    if (typeof data.merchant === "string") { this.merchant = data.merchant; }
+    if (typeof data.period === "string") { this.period = data.period; }
+    if (typeof data.amount === "string") { this.amount = data.amount; }
+    if (typeof data.status === "string") { this.status = data.status; }
+    if (typeof data.first === "string") { this.first = data.first; }
+    if (typeof data.last === "string") { this.last = data.last; }
+    if (typeof data.next === "string") { this.next = data.next; }
+    const increase = data.price_increase;
+    if (
+      increase &&
+      typeof increase.from === "string" &&
+      typeof increase.to === "string" &&
+      typeof increase.date === "string"
+    ) {
+      this.priceIncrease = increase;
+    }
+    this.transactions = [];
+    if (Array.isArray(data.transactions)) {
+      data.transactions.forEach((t) => {
+        if (typeof t === "string") {
+          this.transactions.push(t);
+        }
+      });
+    }
+  }
+}
+
+export class RepeatedTransactionsData {
+  public recurring: RecurringCharge[];
+
+  constructor(data: { [part: string]: any }) {
+    this.recurring = [];
+    if (Array.isArray(data.recurring)) {
+      data.recurring.forEach((r) => {
+        if (r && typeof r === "object") {
+          this.recurring.push(new RecurringCharge(r));
+        }
+      });
+    }
+  }
+}
@@ -6,10 +6,23 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-import { CashflowData, CategoriesData, IncomeExpenseRatioData, RollingBudgetData } from "util/data";
+import {
+  CashflowData,
+  CategoriesData,
+  DuplicateChargesData,
+  IncomeExpenseRatioData,
+  OwnerSpendingData,
+  RepeatedTransactionsData,
+  RollingBudgetData,
+  SettlementData,
+} from "util/data";
 
 export type ReportData =
   | RollingBudgetData
   | CashflowData
   | CategoriesData
-  | IncomeExpenseRatioData;
+  | IncomeExpenseRatioData
+  | RepeatedTransactionsData
+  | DuplicateChargesData
+  | SettlementData
+  | OwnerSpendingData;
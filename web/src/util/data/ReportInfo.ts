@@ -9,10 +9,14 @@
 import {
   CashflowData,
   CategoriesData,
+  DuplicateChargesData,
   IncomeExpenseRatioData,
+  OwnerSpendingData,
+  RepeatedTransactionsData,
   ReportData,
   RollingBudgetConfig,
   RollingBudgetData,
+  SettlementData,
   TimedReportData,
   UIConfig,
 } from "util/data";
@@ -22,7 +26,14 @@ export class ReportInfo {
   public config:
     | RollingBudgetConfig
     | {
-      type: "Cashflow" | "Categories" | "IncomeExpenseRatio";
+      type:
+        | "Cashflow"
+        | "Categories"
+        | "IncomeExpenseRatio"
+        | "RepeatedTransactions"
+        | "DuplicateCharges"
+        | "Settlement"
+        | "OwnerSpending";
     };
   public uiConfig: UIConfig;
   public skipTags?: string[];
@@ -74,6 +85,14 @@ export class ReportInfo {
         return new CategoriesData(data);
       case "IncomeExpenseRatio":
         return new IncomeExpenseRatioData(data);
+      case "RepeatedTransactions":
+        return new RepeatedTransactionsData(data);
+      case "DuplicateCharges":
+        return new DuplicateChargesData(data);
+      case "Settlement":
+        return new SettlementData(data);
+      case "OwnerSpending":
+        return new OwnerSpendingData(data);
       default:
         return null;
     }
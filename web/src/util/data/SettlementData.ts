@@ -0,0 +1,69 @@
+// Copyright 2019 Zachary Bush.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+import { RollingBudgetData, Timeseries } from "util/data";
+
+export interface IPayment {
+  from: string;
+  to: string;
+  amount: string;
+}
+
+export class SettlementData {
+  public balances: Map<string, string>;
+  public payments: IPayment[];
+  public settleUps: string[];
+  public transactions: string[];
+  public timeseries?: Timeseries<{}>;
+
+  constructor(data: { [part: string]: any }) {
+    this.balances = new Map();
+    if (typeof data.balances === "object") {
+      Object.entries(data.balances).forEach(([k, v]) => {
+        if (typeof k === "string" && typeof v === "string") {
+          this.balances.set(k, v);
+        }
+      });
+    }
+    this.payments = [];
+    if (Array.isArray(data.payments)) {
+      data.payments.forEach((p) => {
+        if (
+          p &&
+          typeof p.from === "string" &&
+          typeof p.to === "string" &&
+          typeof p.amount === "string"
+        ) {
+          this.payments.push(p);
+        }
+      });
+    }
+    this.settleUps = [];
+    if (Array.isArray(data.settle_ups)) {
+      data.settle_ups.forEach((t) => {
+        if (typeof t === "string") {
+          this.settleUps.push(t);
+        }
+      });
+    }
+    this.transactions = [];
+    if (Array.isArray(data.transactions)) {
+      data.transactions.forEach((t) => {
+        if (typeof t === "string") {
+          this.transactions.push(t);
+        }
+      });
+    }
+    if (data.timeseries && Array.isArray(data.timeseries)) {
+      this.timeseries = new Timeseries(
+        data.timeseries,
+        RollingBudgetData.parseTsDatum,
+      );
+    }
+  }
+}
@@ -4,13 +4,17 @@ export * from "./CashflowData";
 export * from "./CashflowTsDatum";
 export * from "./CategoriesCategory";
 export * from "./CategoriesData";
+export * from "./DuplicateChargesData";
 export * from "./IncomeExpenseRatioData";
 export * from "./IncomeExpenseRatioDatum";
+export * from "./OwnerSpendingData";
+export * from "./RepeatedTransactionsData";
 export * from "./Report";
 export * from "./ReportData";
 export * from "./ReportInfo";
 export * from "./RollingBudgetConfig";
 export * from "./RollingBudgetData";
+export * from "./SettlementData";
 export * from "./TimedReportData";
 export * from "./Timeseries";
 export * from "./UIConfig";
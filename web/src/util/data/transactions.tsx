@@ -2,6 +2,62 @@ import Money from "components/Money";
 import Chip from "material-ui/Chip";
 import * as React from "react";
 
+export interface Change {
+  processor: string;
+  rule: string;
+  field: string;
+  value: string;
+}
+
+function parseProvenance(data: any): Change[] {
+  if (!Array.isArray(data)) {
+    return [];
+  }
+  return data.filter((c) =>
+    c &&
+    typeof c.processor === "string" &&
+    typeof c.rule === "string" &&
+    typeof c.field === "string" &&
+    typeof c.value === "string",
+  );
+}
+
+export interface Split {
+  amount: string;
+  category: string;
+  person: string;
+  tags: string[];
+}
+
+function parseSplits(data: any): Split[] {
+  if (!Array.isArray(data)) {
+    return [];
+  }
+  return data.filter((s) =>
+    s &&
+    typeof s.amount === "string" &&
+    typeof s.category === "string" &&
+    typeof s.person === "string" &&
+    Array.isArray(s.tags),
+  );
+}
+
+export interface OwnerShare {
+  person: string;
+  percent: number;
+}
+
+function parseOwnerShares(data: any): OwnerShare[] {
+  if (!Array.isArray(data)) {
+    return [];
+  }
+  return data.filter((s) =>
+    s &&
+    typeof s.person === "string" &&
+    typeof s.percent === "number",
+  );
+}
+
 export class Transaction {
 
   public static parse(data: any): Transaction | null {
@@ -58,6 +114,11 @@ export class Transaction {
   public tags: string[];
   public transactionType: string;
   public transferDestinationAccount?: string;
+  public provenance: Change[];
+  public refundedBy: string[];
+  public splits: Split[];
+  public amortizedFrom?: string;
+  public ownerShares: OwnerShare[];
 
   constructor(
     accountName: string,
@@ -72,7 +133,14 @@ export class Transaction {
     person: string,
     tags: any[],
     transactionType: string,
-    data?: { transferDestinationAccount?: any },
+    data?: {
+      transferDestinationAccount?: any,
+      provenance?: any,
+      refunded_by?: any,
+      splits?: any,
+      amortized_from?: any,
+      owner_shares?: any,
+    },
   ) {
     this.accountName = accountName;
     this.amount = amount;
@@ -98,6 +166,20 @@ export class Transaction {
     ) {
       this.transferDestinationAccount = data.transferDestinationAccount;
     }
+    this.provenance = parseProvenance(data && data.provenance);
+    this.splits = parseSplits(data && data.splits);
+    this.ownerShares = parseOwnerShares(data && data.owner_shares);
+    if (data && typeof data.amortized_from === "string") {
+      this.amortizedFrom = data.amortized_from;
+    }
+    this.refundedBy = [];
+    if (data && Array.isArray(data.refunded_by)) {
+      data.refunded_by.forEach((uid: any) => {
+        if (typeof uid === "string") {
+          this.refundedBy.push(uid);
+        }
+      });
+    }
   }
 
   public render(name: string): null | string | React.ReactNode {
@@ -129,7 +211,33 @@ export class Transaction {
       case "originalDescription":
         return this.originalDescription;
       case "person":
+        if (this.ownerShares.length > 0) {
+          return this.ownerShares
+            .map((share) => `${share.person} ${Math.round(share.percent)}%`)
+            .join(", ");
+        }
         return this.person;
+      case "splits":
+        if (this.splits.length === 0) { return null; }
+        return this.splits.map((split, i) => (
+          <div key={i}>
+            <Money amount={split.amount} invert={this.transactionType === "Debit"} />
+            {` ${split.category}`}
+            {split.person ? ` (${split.person})` : ""}
+            {split.tags.map((tag) => <Chip key={tag}>{tag}</Chip>)}
+          </div>
+        ));
+      case "amortizedFrom":
+        return this.amortizedFrom || null;
+      case "refundedBy":
+        return this.refundedBy.length > 0 ? this.refundedBy.join(", ") : null;
+      case "provenance":
+        if (this.provenance.length === 0) { return null; }
+        return this.provenance.map((change, i) => (
+          <div key={i}>
+            {`${change.processor}: ${change.field} = ${change.value} (${change.rule})`}
+          </div>
+        ));
       case "tags":
         return this.tags.map((tag) => <Chip key={tag}>{tag}</Chip>);
       case "transactionType":
@@ -139,15 +247,46 @@ export class Transaction {
   }
 }
 
-export function parseTransactions(transactions: {}): Map<string, Transaction> {
-  const parsedTransactions = new Map();
+export interface DroppedTransaction {
+  transaction: Transaction;
+  processor: string;
+  reason: string;
+}
+
+function parseTransactionMap<T>(
+  entries: any,
+  parse: (data: any) => T | null,
+): Map<string, T> {
+  const parsed = new Map();
+
+  if (entries && typeof entries === "object") {
+    Object.entries(entries).forEach(([uid, entry]) => {
+      if (typeof uid === "string") {
+        const t = parse(entry);
+        if (t) { parsed.set(uid, t); }
+      }
+    });
+  }
+
+  return parsed;
+}
+
+export function parseTransactions(list: any): Map<string, Transaction> {
+  return parseTransactionMap(list && list.transactions, Transaction.parse);
+}
 
-  Object.entries(transactions).forEach(([uid, transaction]) => {
-    if (typeof uid === "string") {
-      const t = Transaction.parse(transaction);
-      if (t) { parsedTransactions.set(uid, t); }
+export function parseDroppedTransactions(
+  list: any,
+): Map<string, DroppedTransaction> {
+  return parseTransactionMap(list && list.dropped, (data) => {
+    const transaction = data && Transaction.parse(data.transaction);
+    if (
+      transaction &&
+      typeof data.processor === "string" &&
+      typeof data.reason === "string"
+    ) {
+      return { transaction, processor: data.processor, reason: data.reason };
     }
+    return null;
   });
-
-  return parsedTransactions;
 }